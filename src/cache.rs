@@ -0,0 +1,119 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::process::Command;
+
+use crate::output::{done, warning, Traced};
+use crate::process::Timed;
+use crate::runtime::Runtime;
+
+/// Package manager caches shared between jails: volume name and path under the home directory
+const CACHES: [(&str, &str); 3] = [
+    ("jail-cache-cargo", ".cargo/registry"),
+    ("jail-cache-npm", ".npm"),
+    ("jail-cache-pip", ".cache/pip"),
+];
+
+/// Volume mounts for the shared caches in a home directory. Volumes are named,
+/// so each runtime keeps its own set.
+pub(crate) fn mount_args(home_dir: &str) -> Vec<String> {
+    CACHES
+        .iter()
+        .flat_map(|(volume, path)| {
+            [
+                "-v".to_string(),
+                format!("{}:{}/{}", volume, home_dir, path),
+            ]
+        })
+        .collect()
+}
+
+/// Hand the cache mountpoints, and parent directories the runtime had to create
+/// for them, to the container's user. Runtimes create missing mountpoints as root,
+/// which would otherwise leave e.g. ~/.cargo unwritable when the image doesn't have it.
+const CHOWN_SCRIPT: &str = r#"
+for dir in "$@"; do
+    chown "$JAIL_USER" "$dir"
+    parent=$(dirname "$dir")
+    if [ "$parent" != "$JAIL_HOME" ] && [ "$(stat -c %u "$parent")" = 0 ]; then
+        chown "$JAIL_USER" "$parent"
+    fi
+done
+"#;
+
+/// Make the shared caches writable by the container's user, warning on failure
+pub(crate) fn fix_ownership(runtime: Runtime, container_id: &str, user: &str, home_dir: &str) {
+    if user == "root" || user == "0" {
+        return;
+    }
+
+    let status = Command::new(runtime.command())
+        .args(["exec", "-u", "root", "-e", "JAIL_USER", "-e", "JAIL_HOME"])
+        .args([container_id, "sh", "-c", CHOWN_SCRIPT, "sh"])
+        .args(
+            CACHES
+                .iter()
+                .map(|(_, path)| format!("{}/{}", home_dir, path)),
+        )
+        .env("JAIL_USER", user)
+        .env("JAIL_HOME", home_dir)
+        .traced()
+        .timed_status();
+
+    if !status.is_ok_and(|status| status.success()) {
+        warning!(
+            "Failed to set up shared caches, package managers may not be able to write to them"
+        );
+    }
+}
+
+/// Remove the shared cache volumes
+pub fn clear(runtime: Runtime) -> Result<()> {
+    let mut removed = 0;
+    for (volume, _) in CACHES {
+        let exists = Command::new(runtime.command())
+            .args(["volume", "inspect", volume])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .traced()
+            .timed_status()
+            .context("Failed to inspect volume")?
+            .success();
+        if !exists {
+            continue;
+        }
+
+        let output = Command::new(runtime.command())
+            .args(["volume", "rm", volume])
+            .traced()
+            .timed_output()
+            .context("Failed to remove volume")?;
+        if !output.status.success() {
+            bail!(
+                "Failed to remove {} (stop jails using it with 'jail stop --all'): {}",
+                volume,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        removed += 1;
+    }
+
+    if removed == 0 {
+        println!("No shared caches to clear.");
+    } else {
+        done!("Shared caches cleared");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_args() {
+        let args = mount_args("/home/dev");
+        assert_eq!(args.len(), 6);
+        assert_eq!(args[1], "jail-cache-cargo:/home/dev/.cargo/registry");
+        assert_eq!(args[5], "jail-cache-pip:/home/dev/.cache/pip");
+    }
+}
@@ -0,0 +1,143 @@
+use clap::Command;
+use clap_complete::Shell;
+
+/// Command that prints one jail name per line, used for dynamic completion
+const LIST_NAMES: &str = "jail list --quiet 2>/dev/null";
+
+/// Print the completion script for a shell to stdout
+pub fn print(shell: Shell, cmd: &mut Command) {
+    print!("{}", generate(shell, cmd));
+}
+
+/// Generate the completion script, adding jail name completion where supported
+fn generate(shell: Shell, cmd: &mut Command) -> String {
+    let mut buf = Vec::new();
+    clap_complete::generate(shell, cmd, "jail", &mut buf);
+    let script = String::from_utf8_lossy(&buf).into_owned();
+    let commands = name_commands(cmd);
+
+    match shell {
+        Shell::Bash => bash(script, &commands),
+        Shell::Zsh => zsh(script),
+        Shell::Fish => fish(script, &commands),
+        _ => script,
+    }
+}
+
+/// Subcommands taking optional jail names/filters as their first positional argument
+fn name_commands(cmd: &Command) -> Vec<String> {
+    cmd.get_subcommands()
+        .filter(|sub| {
+            sub.get_positionals().next().is_some_and(|arg| {
+                ["name", "names"].contains(&arg.get_id().as_str()) && !arg.is_required_set()
+            })
+        })
+        .map(|sub| sub.get_name().to_string())
+        .collect()
+}
+
+fn bash(script: String, commands: &[String]) -> String {
+    let helper = format!(
+        r#"
+_jail_with_names() {{
+    local i sub=""
+    for i in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        if [[ "$i" != -* ]]; then
+            sub="$i"
+            break
+        fi
+    done
+
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$sub" in
+        {})
+            if [[ "$cur" != -* && "$prev" == "$sub" ]]; then
+                COMPREPLY=($(compgen -W "$({})" -- "$cur"))
+                return 0
+            fi
+            ;;
+    esac
+    _jail "$@"
+}}
+"#,
+        commands.join("|"),
+        LIST_NAMES
+    );
+
+    script.replace("complete -F _jail ", "complete -F _jail_with_names ") + &helper
+}
+
+fn zsh(script: String) -> String {
+    let helper = format!(
+        r#"
+_jail_names() {{
+    local -a names
+    names=(${{(f)"$({})"}})
+    compadd -a names
+}}
+"#,
+        LIST_NAMES
+    );
+
+    // Optional `name` positionals are jail names/filters
+    let script = script
+        .lines()
+        .map(|line| {
+            if line.starts_with("'::name") {
+                line.replace(":_default'", ":_jail_names'")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match script.split_once('\n') {
+        Some((compdef, rest)) => format!("{}\n{}\n{}\n", compdef, helper, rest),
+        None => script,
+    }
+}
+
+fn fish(mut script: String, commands: &[String]) -> String {
+    for command in commands {
+        script.push_str(&format!(
+            "complete -c jail -n \"__fish_jail_using_subcommand {}\" -f -a \"({})\"\n",
+            command, LIST_NAMES
+        ));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn test_command() -> Command {
+        Command::new("jail")
+            .subcommand(Command::new("enter").arg(Arg::new("name")))
+            .subcommand(Command::new("create").arg(Arg::new("name").required(true)))
+            .subcommand(Command::new("rm").arg(Arg::new("names").num_args(0..)))
+            .subcommand(Command::new("status"))
+    }
+
+    #[test]
+    fn test_name_commands() {
+        assert_eq!(name_commands(&test_command()), ["enter", "rm"]);
+    }
+
+    #[test]
+    fn test_dynamic_name_completion() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = generate(shell, &mut test_command());
+            assert!(script.contains(LIST_NAMES), "{} script", shell);
+        }
+
+        let zsh = generate(Shell::Zsh, &mut test_command());
+        assert!(zsh.starts_with("#compdef jail\n"));
+        assert!(zsh.contains("'::name:_jail_names'"));
+        // Required names are new jail names, not existing ones
+        assert!(zsh.contains("':name:_default'"));
+    }
+}
@@ -1,18 +1,106 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::image::{Package, Preset};
+use crate::network::NetworkMode;
+use crate::output::{done, warning};
+use crate::port::PortMapping;
 use crate::runtime::Runtime;
+use crate::shell::Shell;
 
 #[derive(Debug, Default, Serialize, Deserialize)]
-pub struct Config {
+pub(crate) struct Config {
     /// Override runtime selection (podman or docker)
     pub runtime: Option<Runtime>,
+    /// Seconds to wait for a Docker daemon or Podman machine that is still starting (default: 20)
+    pub daemon_wait: Option<u64>,
+    /// Seconds before a runtime query is assumed hung and stopped, 0 for no limit (default: 30)
+    pub command_timeout: Option<u64>,
+    /// Host `owner/repo` sources are cloned from (default: github.com)
+    pub default_git_host: Option<String>,
+    /// Default history depth for `jail clone` (full history if unset)
+    pub clone_depth: Option<u32>,
+    /// Image to pull and use instead of building the embedded Dockerfile
+    pub base_image: Option<String>,
+    /// Build the preset's image locally, under base_image's name, when pulling it fails (default: false)
+    pub build_if_missing: Option<bool>,
+    /// Toolchain preset for new jails (default: full)
+    pub default_preset: Option<Preset>,
+    /// apt packages added to the built base images (not to a configured base_image)
+    pub extra_packages: Option<Vec<Package>>,
+    /// Install the docker CLI and Compose plugin in built base images, for jails
+    /// created with --docker-socket (default: false)
+    pub docker_cli: Option<bool>,
+    /// Ports new jails publish, besides their own `--port`s
+    pub default_ports: Option<Vec<PortMapping>>,
+    /// Mounts (`host:container[:ro]`, absolute or `~/` host paths) for new jails
+    pub default_mounts: Option<Vec<String>>,
+    /// Environment variables (`KEY=VALUE`, or `KEY` to copy from the host) for new jails
+    pub default_env: Option<Vec<String>>,
+    /// Shell used in jails that don't set their own (bash if unset)
+    pub shell: Option<Shell>,
+    /// Copy the host's git user.name/user.email into jails (default: true)
+    pub propagate_gitconfig: Option<bool>,
+    /// Pass the host's timezone and locale (TZ, LANG, LC_ALL) into new containers (default: true)
+    pub propagate_timezone: Option<bool>,
+    /// Pass the host's HTTP_PROXY/HTTPS_PROXY/NO_PROXY into new containers (default: true)
+    pub propagate_proxy: Option<bool>,
+    /// Let new containers trust the hosts in the host's ~/.ssh/known_hosts (default: true)
+    pub propagate_known_hosts: Option<bool>,
+    /// Relabel bind mounts for SELinux with `:Z` (default: when SELinux is enforcing)
+    pub selinux_relabel: Option<bool>,
+    /// Lock down new jails' containers for untrusted code (default: false)
+    pub hardened: Option<bool>,
+    /// Make the host reachable from jails and set JAIL_HOST_ADDR to its address (default: true)
+    pub host_gateway: Option<bool>,
+    /// Run `jail pull`'s git commands inside the container, with the forwarded SSH agent (default: false)
+    pub pull_in_container: Option<bool>,
+    /// Share cargo, npm and pip caches between all jails (default: false)
+    pub shared_caches: Option<bool>,
+    /// Pass the host's Claude Code login (~/.claude, ANTHROPIC_API_KEY) into new jails (default: false)
+    pub ai_credentials: Option<bool>,
+    /// Where removed jails and kept workspaces are moved (default: ~/.local/share/jail/trash)
+    pub trash_dir: Option<String>,
+    /// Days removed jails stay in the trash before they're deleted (default: 7)
+    pub trash_retention_days: Option<u32>,
+    /// Container user for jails that don't set their own (default: dev)
+    pub user: Option<String>,
+    /// Network mode for jails that don't set their own (default: host on Linux, bridge on macOS)
+    pub default_network: Option<NetworkMode>,
+    /// Enter new jails right after clone/create (default: true)
+    pub auto_enter: Option<bool>,
+    /// Run interactive shells in a tmux session that survives disconnects (default: false)
+    pub tmux: Option<bool>,
+    /// Preselect the most recently used jail when asking which jail to use (default: false)
+    pub prefer_recent: Option<bool>,
+    /// Editor for `jail code`: code, code-insiders, cursor, windsurf, another
+    /// binary, or a command template containing {uri}
+    pub editor: Option<String>,
+    /// Dotfiles repository installed in new jails
+    pub dotfiles: Option<Dotfiles>,
+}
+
+/// `[dotfiles]` section: a repository cloned and installed inside each new jail
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Dotfiles {
+    /// Git URL of the dotfiles repository
+    pub repository: String,
+    /// Where to clone it inside the container
+    #[serde(default = "default_dotfiles_path")]
+    pub target_path: String,
+    /// Command run from the clone (default: install.sh or setup.sh if present)
+    pub install_command: Option<String>,
+}
+
+fn default_dotfiles_path() -> String {
+    "~/.dotfiles".to_string()
 }
 
 /// Get the config directory path (~/.config/jail/)
-pub fn config_dir() -> Result<PathBuf> {
+pub(crate) fn config_dir() -> Result<PathBuf> {
     if let Some(proj_dirs) = ProjectDirs::from("", "", "jail") {
         Ok(proj_dirs.config_dir().to_path_buf())
     } else {
@@ -23,7 +111,7 @@ pub fn config_dir() -> Result<PathBuf> {
 }
 
 /// Get the data directory path (~/.local/share/jail/)
-pub fn data_dir() -> Result<PathBuf> {
+pub(crate) fn data_dir() -> Result<PathBuf> {
     if let Some(proj_dirs) = ProjectDirs::from("", "", "jail") {
         Ok(proj_dirs.data_dir().to_path_buf())
     } else {
@@ -34,17 +122,33 @@ pub fn data_dir() -> Result<PathBuf> {
 }
 
 /// Get the jails directory path (~/.local/share/jail/jails/)
-pub fn jails_dir() -> Result<PathBuf> {
+pub(crate) fn jails_dir() -> Result<PathBuf> {
     Ok(data_dir()?.join("jails"))
 }
 
+/// Get the directory removed jails and kept workspaces are moved to (~/.local/share/jail/trash/ by default)
+pub(crate) fn trash_dir() -> Result<PathBuf> {
+    match load()?.trash_dir {
+        Some(dir) => match dir.strip_prefix("~/") {
+            Some(rest) => Ok(dirs_home()?.join(rest)),
+            None => Ok(PathBuf::from(dir)),
+        },
+        None => Ok(data_dir()?.join("trash")),
+    }
+}
+
 fn dirs_home() -> Result<PathBuf> {
     dirs::home_dir().context("Could not determine home directory")
 }
 
+/// Path of the config file (~/.config/jail/config.toml)
+pub fn config_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
 /// Load configuration from file
-pub fn load() -> Result<Config> {
-    let config_path = config_dir()?.join("config.toml");
+pub(crate) fn load() -> Result<Config> {
+    let config_path = config_path()?;
 
     if !config_path.exists() {
         return Ok(Config::default());
@@ -57,9 +161,233 @@ pub fn load() -> Result<Config> {
         .with_context(|| format!("Failed to parse config file: {}", config_path.display()))
 }
 
-/// Get runtime override from config or environment
-pub fn get_runtime_override() -> Result<Option<Runtime>> {
-    // Check environment variable first
+/// Keys accepted by `jail config`, with the values they take
+const KEYS: [(&str, &str); 35] = [
+    ("runtime", "podman or docker"),
+    ("daemon_wait", "seconds"),
+    ("command_timeout", "seconds, 0 for no limit"),
+    ("clone_depth", "number of commits"),
+    (
+        "default_git_host",
+        "host for owner/repo sources, e.g. gitlab.com",
+    ),
+    ("base_image", "image reference"),
+    ("build_if_missing", "true or false"),
+    ("default_preset", "full, node, rust, python or minimal"),
+    (
+        "extra_packages",
+        "comma-separated apt packages, e.g. jq,ripgrep",
+    ),
+    ("docker_cli", "true or false"),
+    ("default_ports", "comma-separated ports, e.g. 3000,8080:80"),
+    (
+        "default_mounts",
+        "comma-separated host:container[:ro] mounts",
+    ),
+    ("default_env", "comma-separated KEY=VALUE or KEY variables"),
+    ("shell", "bash, zsh or fish"),
+    ("user", "container user"),
+    (
+        "editor",
+        "code, code-insiders, cursor, windsurf, a binary or a command with {uri}",
+    ),
+    ("propagate_gitconfig", "true or false"),
+    ("propagate_timezone", "true or false"),
+    ("propagate_proxy", "true or false"),
+    ("propagate_known_hosts", "true or false"),
+    ("host_gateway", "true or false"),
+    ("pull_in_container", "true or false"),
+    ("shared_caches", "true or false"),
+    ("ai_credentials", "true or false"),
+    ("selinux_relabel", "true or false"),
+    (
+        "trash_dir",
+        "directory for removed jails and kept workspaces",
+    ),
+    ("trash_retention_days", "number of days"),
+    ("default_network", "host, bridge or none"),
+    ("hardened", "true or false"),
+    ("auto_enter", "true or false"),
+    ("tmux", "true or false"),
+    ("prefer_recent", "true or false"),
+    ("dotfiles.repository", "git URL"),
+    ("dotfiles.target_path", "path inside the container"),
+    ("dotfiles.install_command", "shell command"),
+];
+
+/// The raw contents of the config file, keeping keys this version doesn't know
+fn load_table() -> Result<toml::Table> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    content
+        .parse()
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))
+}
+
+fn save_table(table: &toml::Table) -> Result<()> {
+    let path = config_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    std::fs::write(&path, toml::to_string_pretty(table)?)
+        .with_context(|| format!("Failed to write config file: {}", path.display()))
+}
+
+/// Check that a key is one of `KEYS`, splitting it into its table and field
+fn parse_key(key: &str) -> Result<(Option<&str>, &str)> {
+    if !KEYS.iter().any(|(k, _)| *k == key) {
+        bail!(
+            "Unknown config key '{}'. Valid keys:\n  {}",
+            key,
+            KEYS.iter()
+                .map(|(k, values)| format!("{:<26}{}", k, values))
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        );
+    }
+    Ok(match key.split_once('.') {
+        Some((table, field)) => (Some(table), field),
+        None => (None, key),
+    })
+}
+
+fn get_value<'a>(table: &'a toml::Table, key: &str) -> Result<Option<&'a toml::Value>> {
+    Ok(match parse_key(key)? {
+        (Some(section), field) => table.get(section).and_then(|s| s.get(field)),
+        (None, field) => table.get(field),
+    })
+}
+
+/// Set a key, checking that the result still loads as a `Config`. Numbers and
+/// booleans are stored as such when the key accepts them, comma-separated values
+/// as a list when it takes one, and anything else as a string.
+fn set_value(table: &mut toml::Table, key: &str, value: &str) -> Result<()> {
+    let (section, field) = parse_key(key)?;
+
+    let mut candidates = Vec::new();
+    if let Ok(literal) = value.parse::<i64>() {
+        candidates.push(toml::Value::Integer(literal));
+    } else if let Ok(literal) = value.parse::<bool>() {
+        candidates.push(toml::Value::Boolean(literal));
+    }
+    candidates.push(toml::Value::String(value.to_string()));
+    candidates.push(toml::Value::Array(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| toml::Value::String(item.to_string()))
+            .collect(),
+    ));
+
+    let mut error = None;
+    for candidate in candidates {
+        let mut updated = table.clone();
+        let target = match section {
+            Some(section) => match updated
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            {
+                toml::Value::Table(target) => target,
+                _ => bail!("'{}' in the config file is not a table", section),
+            },
+            None => &mut updated,
+        };
+        target.insert(field.to_string(), candidate);
+
+        match updated.clone().try_into::<Config>() {
+            Ok(_) => {
+                *table = updated;
+                return Ok(());
+            }
+            Err(e) => error = Some(e),
+        }
+    }
+
+    let values = KEYS.iter().find(|(k, _)| *k == key).map_or("", |(_, v)| v);
+    Err(anyhow::anyhow!(
+        "Invalid value '{}' for {} (expected {}): {}",
+        value,
+        key,
+        values,
+        error.map(|e| e.message().to_string()).unwrap_or_default()
+    ))
+}
+
+/// Remove a key, returning whether it was set
+fn unset_value(table: &mut toml::Table, key: &str) -> Result<bool> {
+    let mut updated = table.clone();
+    let removed = remove_value(&mut updated, key)?;
+    if let Err(e) = updated.clone().try_into::<Config>() {
+        bail!("Can't unset {}: {}", key, e.message());
+    }
+    *table = updated;
+    Ok(removed)
+}
+
+fn remove_value(table: &mut toml::Table, key: &str) -> Result<bool> {
+    Ok(match parse_key(key)? {
+        (Some(section), field) => {
+            let Some(toml::Value::Table(target)) = table.get_mut(section) else {
+                return Ok(false);
+            };
+            let removed = target.remove(field).is_some();
+            if target.is_empty() {
+                table.remove(section);
+            }
+            removed
+        }
+        (None, field) => table.remove(field).is_some(),
+    })
+}
+
+/// Print one config value, or the whole config file
+pub fn get(key: Option<&str>) -> Result<()> {
+    let table = load_table()?;
+    match key {
+        Some(key) => match get_value(&table, key)? {
+            Some(toml::Value::String(s)) => println!("{}", s),
+            Some(value) => println!("{}", value),
+            None => {}
+        },
+        None => print!("{}", toml::to_string_pretty(&table)?),
+    }
+    Ok(())
+}
+
+/// Set a config value, creating the config file if needed
+pub fn set(key: &str, value: &str) -> Result<()> {
+    let mut table = load_table()?;
+    set_value(&mut table, key, value)?;
+    save_table(&table)?;
+    done!("Set {} = {}", key, value);
+    Ok(())
+}
+
+/// Remove a config value, falling back to its default
+pub fn unset(key: &str) -> Result<()> {
+    let mut table = load_table()?;
+    if unset_value(&mut table, key)? {
+        save_table(&table)?;
+        done!("Unset {}", key);
+    } else {
+        warning!("{} is not set", key);
+    }
+    Ok(())
+}
+
+/// Get runtime override from `requested` (`--runtime`), the environment or config
+pub(crate) fn get_runtime_override(requested: Option<Runtime>) -> Result<Option<Runtime>> {
+    if let Some(runtime) = requested {
+        return Ok(Some(runtime));
+    }
+
+    // Then the environment variable
     if let Ok(runtime_str) = std::env::var("JAIL_RUNTIME") {
         let runtime = match runtime_str.to_lowercase().as_str() {
             "podman" => Runtime::Podman,
@@ -77,6 +405,33 @@ pub fn get_runtime_override() -> Result<Option<Runtime>> {
     Ok(config.runtime)
 }
 
+/// Whether bind mounts need SELinux relabeling: the config value, or whether
+/// SELinux is enforcing on this host
+pub(crate) fn selinux_relabel() -> Result<bool> {
+    Ok(load()?
+        .selinux_relabel
+        .unwrap_or_else(crate::runtime::selinux_enforcing))
+}
+
+/// Whether a base image that can't be pulled is built locally instead
+pub fn build_if_missing() -> Result<bool> {
+    Ok(load()?.build_if_missing.unwrap_or(false))
+}
+
+/// Get base image override from config or environment
+pub fn get_base_image_override() -> Result<Option<String>> {
+    // Check environment variable first
+    if let Ok(image) = std::env::var("JAIL_BASE_IMAGE") {
+        if !image.trim().is_empty() {
+            return Ok(Some(image.trim().to_string()));
+        }
+    }
+
+    // Check config file
+    let config = load()?;
+    Ok(config.base_image.filter(|image| !image.trim().is_empty()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,5 +440,126 @@ mod tests {
     fn test_default_config() {
         let config = Config::default();
         assert!(config.runtime.is_none());
+        assert!(config.clone_depth.is_none());
+        assert!(config.base_image.is_none());
+    }
+
+    #[test]
+    fn test_set_config_values() {
+        let mut table = toml::Table::new();
+        set_value(&mut table, "runtime", "docker").unwrap();
+        set_value(&mut table, "clone_depth", "1").unwrap();
+        set_value(&mut table, "auto_enter", "false").unwrap();
+        set_value(&mut table, "base_image", "ghcr.io/acme/dev:1").unwrap();
+        set_value(&mut table, "dotfiles.repository", "git@example.com:me/dots").unwrap();
+        // Values that look like numbers stay strings where a string is expected
+        set_value(&mut table, "user", "1000").unwrap();
+
+        let config: Config = toml::from_str(&toml::to_string_pretty(&table).unwrap()).unwrap();
+        assert_eq!(config.runtime, Some(Runtime::Docker));
+        assert_eq!(config.clone_depth, Some(1));
+        assert_eq!(config.auto_enter, Some(false));
+        assert_eq!(config.user.as_deref(), Some("1000"));
+        assert_eq!(
+            config.dotfiles.unwrap().repository,
+            "git@example.com:me/dots"
+        );
+        assert_eq!(
+            get_value(&table, "runtime").unwrap(),
+            Some(&toml::Value::String("docker".into()))
+        );
+
+        assert!(set_value(&mut table, "runtime", "dockr").is_err());
+        assert!(set_value(&mut table, "auto_enter", "maybe").is_err());
+        assert!(set_value(&mut table, "nope", "1").is_err());
+        assert!(get_value(&table, "nope").is_err());
+        assert_eq!(table["runtime"].as_str(), Some("docker"));
+
+        set_value(&mut table, "dotfiles.target_path", "~/dots").unwrap();
+        assert!(unset_value(&mut table, "dotfiles.repository").is_err());
+        assert!(unset_value(&mut table, "dotfiles.target_path").unwrap());
+        assert!(unset_value(&mut table, "dotfiles.repository").unwrap());
+        assert!(!table.contains_key("dotfiles"));
+        assert!(!unset_value(&mut table, "shell").unwrap());
+    }
+
+    #[test]
+    fn test_set_list_values() {
+        let mut table = toml::Table::new();
+        set_value(&mut table, "default_ports", "3000, 5432").unwrap();
+        set_value(&mut table, "default_env", "RUST_LOG=debug").unwrap();
+        let config: Config = toml::from_str(&toml::to_string_pretty(&table).unwrap()).unwrap();
+        assert_eq!(
+            config.default_ports.unwrap(),
+            [
+                "3000".parse().unwrap(),
+                "5432".parse::<PortMapping>().unwrap()
+            ]
+        );
+        assert_eq!(config.default_env.unwrap(), ["RUST_LOG=debug"]);
+        assert!(set_value(&mut table, "default_ports", "3000,http").is_err());
+
+        // Plain port numbers work too when written by hand
+        let config: Config = toml::from_str("default_ports = [3000, \"8080:80\"]").unwrap();
+        assert_eq!(config.default_ports.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_clone_depth() {
+        let config: Config = toml::from_str("clone_depth = 1").unwrap();
+        assert_eq!(config.clone_depth, Some(1));
+    }
+
+    #[test]
+    fn test_parse_shell() {
+        let config: Config = toml::from_str(r#"shell = "fish""#).unwrap();
+        assert_eq!(config.shell, Some(Shell::Fish));
+    }
+
+    #[test]
+    fn test_parse_dotfiles() {
+        let config: Config = toml::from_str(
+            r#"
+            [dotfiles]
+            repository = "git@github.com:me/dotfiles.git"
+            "#,
+        )
+        .unwrap();
+        let dotfiles = config.dotfiles.unwrap();
+        assert_eq!(dotfiles.target_path, "~/.dotfiles");
+        assert!(dotfiles.install_command.is_none());
+    }
+
+    #[test]
+    fn test_parse_default_network() {
+        let config: Config = toml::from_str(r#"default_network = "bridge""#).unwrap();
+        assert_eq!(config.default_network, Some(NetworkMode::Bridge));
+    }
+
+    #[test]
+    fn test_parse_editor() {
+        let config: Config = toml::from_str(r#"editor = "cursor""#).unwrap();
+        assert_eq!(config.editor.as_deref(), Some("cursor"));
+    }
+
+    #[test]
+    fn test_parse_user() {
+        let config: Config = toml::from_str(r#"user = "root""#).unwrap();
+        assert_eq!(config.user.as_deref(), Some("root"));
+    }
+
+    #[test]
+    fn test_parse_default_preset() {
+        let config: Config = toml::from_str(r#"default_preset = "rust""#).unwrap();
+        assert_eq!(config.default_preset, Some(Preset::Rust));
+    }
+
+    #[test]
+    fn test_parse_base_image() {
+        let config: Config = toml::from_str(r#"base_image = "ghcr.io/acme/dev:latest""#).unwrap();
+        assert_eq!(
+            config.base_image.as_deref(),
+            Some("ghcr.io/acme/dev:latest")
+        );
     }
 }
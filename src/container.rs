@@ -0,0 +1,801 @@
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use crate::output::{self, Traced};
+use crate::process::Timed;
+use crate::runtime::Runtime;
+
+/// What `inspect` reports about an existing container
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Inspected {
+    pub running: bool,
+    /// Container name, without Docker's leading slash
+    pub name: String,
+    /// Image the container was created from
+    pub image: String,
+    pub labels: BTreeMap<String, String>,
+}
+
+/// A container as reported by `ps`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ContainerState {
+    pub id: String,
+    pub running: bool,
+}
+
+/// The container operations jails are managed with. `Runtime` runs them with
+/// the docker or podman CLI; tests use an in-memory fake.
+pub(crate) trait ContainerRuntime {
+    /// The runtime being driven, for runtime-specific arguments
+    fn kind(&self) -> Runtime;
+    /// Containers matching a `ps` filter such as `label=k=v` or `name=^x$`,
+    /// including stopped ones with `all`
+    fn ps(&self, filter: &str, all: bool) -> Result<Vec<ContainerState>>;
+    /// State, name and labels of a container, or none if it doesn't exist
+    fn inspect(&self, id: &str) -> Result<Option<Inspected>>;
+    /// Create and start a detached container from `run` arguments, returning its ID
+    fn run(&self, args: &[String]) -> Result<String>;
+    fn start(&self, id: &str) -> Result<()>;
+    fn stop(&self, id: &str) -> Result<()>;
+    fn rm(&self, id: &str) -> Result<()>;
+    fn rename(&self, id: &str, name: &str) -> Result<()>;
+    /// Copy a host file into a container
+    fn cp(&self, src: &Path, id: &str, dest: &str) -> Result<()>;
+    /// Save a container's filesystem as an image
+    fn commit(&self, id: &str, image: &str) -> Result<()>;
+    fn image_exists(&self, image: &str) -> Result<bool>;
+    /// Give an image another name
+    fn tag(&self, image: &str, tag: &str) -> Result<()>;
+    fn rmi(&self, image: &str) -> Result<()>;
+    fn volume_rm(&self, volume: &str) -> Result<()>;
+    /// Copy the contents of one volume into another with a container of
+    /// `image`, returning false if there was no source volume to copy
+    fn copy_volume(&self, image: &str, from: &str, to: &str) -> Result<bool>;
+    /// Copy an image to another runtime (`docker save | podman load`)
+    fn transfer_image(&self, image: &str, to: Runtime) -> Result<()>;
+    /// Copy a volume to another runtime with containers of `image`, which both
+    /// must have, returning false if there was no volume to copy
+    fn transfer_volume(&self, volume: &str, image: &str, to: Runtime) -> Result<bool>;
+    /// Write a volume's contents to a tar file with a container of `image`,
+    /// returning false if there was no volume to save
+    fn save_volume(&self, volume: &str, image: &str, file: &Path) -> Result<bool>;
+    /// Unpack a tar file written by `save_volume` into a volume
+    fn load_volume(&self, volume: &str, image: &str, file: &Path) -> Result<()>;
+    /// Build an image with `build` arguments from a Dockerfile, logging the output to `log`
+    fn build(&self, args: &[String], dockerfile: &str, log: &Path) -> Result<()>;
+    /// Run a command with `exec` arguments attached to the terminal, returning
+    /// whether it succeeded
+    fn exec(&self, args: &[String]) -> Result<bool>;
+}
+
+impl<T: ContainerRuntime + ?Sized> ContainerRuntime for &T {
+    fn kind(&self) -> Runtime {
+        (**self).kind()
+    }
+    fn ps(&self, filter: &str, all: bool) -> Result<Vec<ContainerState>> {
+        (**self).ps(filter, all)
+    }
+    fn inspect(&self, id: &str) -> Result<Option<Inspected>> {
+        (**self).inspect(id)
+    }
+    fn run(&self, args: &[String]) -> Result<String> {
+        (**self).run(args)
+    }
+    fn start(&self, id: &str) -> Result<()> {
+        (**self).start(id)
+    }
+    fn stop(&self, id: &str) -> Result<()> {
+        (**self).stop(id)
+    }
+    fn rm(&self, id: &str) -> Result<()> {
+        (**self).rm(id)
+    }
+    fn rename(&self, id: &str, name: &str) -> Result<()> {
+        (**self).rename(id, name)
+    }
+    fn cp(&self, src: &Path, id: &str, dest: &str) -> Result<()> {
+        (**self).cp(src, id, dest)
+    }
+    fn commit(&self, id: &str, image: &str) -> Result<()> {
+        (**self).commit(id, image)
+    }
+    fn image_exists(&self, image: &str) -> Result<bool> {
+        (**self).image_exists(image)
+    }
+    fn tag(&self, image: &str, tag: &str) -> Result<()> {
+        (**self).tag(image, tag)
+    }
+    fn rmi(&self, image: &str) -> Result<()> {
+        (**self).rmi(image)
+    }
+    fn volume_rm(&self, volume: &str) -> Result<()> {
+        (**self).volume_rm(volume)
+    }
+    fn copy_volume(&self, image: &str, from: &str, to: &str) -> Result<bool> {
+        (**self).copy_volume(image, from, to)
+    }
+    fn transfer_image(&self, image: &str, to: Runtime) -> Result<()> {
+        (**self).transfer_image(image, to)
+    }
+    fn transfer_volume(&self, volume: &str, image: &str, to: Runtime) -> Result<bool> {
+        (**self).transfer_volume(volume, image, to)
+    }
+    fn save_volume(&self, volume: &str, image: &str, file: &Path) -> Result<bool> {
+        (**self).save_volume(volume, image, file)
+    }
+    fn load_volume(&self, volume: &str, image: &str, file: &Path) -> Result<()> {
+        (**self).load_volume(volume, image, file)
+    }
+    fn build(&self, args: &[String], dockerfile: &str, log: &Path) -> Result<()> {
+        (**self).build(args, dockerfile, log)
+    }
+    fn exec(&self, args: &[String]) -> Result<bool> {
+        (**self).exec(args)
+    }
+}
+
+/// Fail with the command's error output unless it succeeded
+fn check(output: Output, what: &str) -> Result<Output> {
+    if !output.status.success() {
+        bail!(
+            "Failed to {}: {}",
+            what,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output)
+}
+
+/// Parse `ps` output formatted as `{id}\t{state}` per line. Podman before 4.0
+/// reports the state as a status like `Up 5 minutes`.
+fn parse_ps(output: &str) -> Vec<ContainerState> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (id, state) = line.trim().split_once('\t')?;
+            Some(ContainerState {
+                id: id.to_string(),
+                running: state == "running" || state.starts_with("Up "),
+            })
+        })
+        .collect()
+}
+
+/// Parse `inspect` output formatted as `{running}\t{name}\t{labels as JSON}`
+fn parse_inspect(output: &str) -> Option<Inspected> {
+    let mut fields = output.trim().splitn(4, '\t');
+    let running = fields.next()? == "true";
+    let name = fields.next()?.trim_start_matches('/').to_string();
+    let image = fields.next()?.to_string();
+    // Containers without labels report null
+    let labels = serde_json::from_str::<Option<BTreeMap<String, String>>>(fields.next()?)
+        .ok()?
+        .unwrap_or_default();
+    Some(Inspected {
+        running,
+        name,
+        image,
+        labels,
+    })
+}
+
+/// Run `producer | consumer`, failing when either fails
+fn pipe(producer: &mut Command, consumer: &mut Command) -> Result<()> {
+    let mut child = producer
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .traced()
+        .spawn()
+        .context("Failed to start command")?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to read command output")?;
+
+    let consumed = consumer
+        .stdin(stdout)
+        .traced()
+        .output()
+        .context("Failed to start command");
+    let produced = child.wait_with_output()?;
+
+    if !produced.status.success() {
+        bail!("{}", String::from_utf8_lossy(&produced.stderr).trim());
+    }
+    let consumed = consumed?;
+    if !consumed.status.success() {
+        bail!("{}", String::from_utf8_lossy(&consumed.stderr).trim());
+    }
+    Ok(())
+}
+
+impl Runtime {
+    /// Run a short runtime query, failing with its error output
+    fn query(&self, args: &[&str], what: &str) -> Result<Output> {
+        let output = Command::new(self.command())
+            .args(args)
+            .traced()
+            .timed_output()
+            .with_context(|| format!("Failed to {}", what))?;
+        check(output, what)
+    }
+
+    fn volume_exists(&self, volume: &str) -> Result<bool> {
+        let status = Command::new(self.command())
+            .args(["volume", "inspect", volume])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .traced()
+            .timed_status()
+            .context("Failed to inspect volume")?;
+        Ok(status.success())
+    }
+}
+
+impl ContainerRuntime for Runtime {
+    fn kind(&self) -> Runtime {
+        *self
+    }
+
+    fn ps(&self, filter: &str, all: bool) -> Result<Vec<ContainerState>> {
+        let mut args = vec!["ps", "--format", "{{.ID}}\t{{.State}}", "-f", filter];
+        if all {
+            args.push("-a");
+        }
+        // A failing ps (e.g. an unsupported filter) just finds nothing
+        let output = Command::new(self.command())
+            .args(args)
+            .traced()
+            .timed_output()
+            .context("Failed to check container status")?;
+        Ok(parse_ps(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn inspect(&self, id: &str) -> Result<Option<Inspected>> {
+        let output = Command::new(self.command())
+            .args([
+                "inspect",
+                "--type",
+                "container",
+                "-f",
+                "{{.State.Running}}\t{{.Name}}\t{{.Config.Image}}\t{{json .Config.Labels}}",
+                id,
+            ])
+            .traced()
+            .timed_output()
+            .context("Failed to inspect container")?;
+        // Failing means there's no such container (any more)
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(parse_inspect(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn run(&self, args: &[String]) -> Result<String> {
+        let output = Command::new(self.command())
+            .args(args)
+            .traced()
+            .output()
+            .context("Failed to create container")?;
+        let output = check(output, "create container")?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn start(&self, id: &str) -> Result<()> {
+        self.query(&["start", id], "start container").map(drop)
+    }
+
+    fn stop(&self, id: &str) -> Result<()> {
+        self.query(&["stop", id], "stop container").map(drop)
+    }
+
+    fn rm(&self, id: &str) -> Result<()> {
+        self.query(&["rm", id], "remove container").map(drop)
+    }
+
+    fn rename(&self, id: &str, name: &str) -> Result<()> {
+        self.query(&["rename", id, name], "rename container")
+            .map(drop)
+    }
+
+    fn cp(&self, src: &Path, id: &str, dest: &str) -> Result<()> {
+        self.query(
+            &["cp", &src.to_string_lossy(), &format!("{}:{}", id, dest)],
+            "copy file into container",
+        )
+        .map(drop)
+    }
+
+    fn commit(&self, id: &str, image: &str) -> Result<()> {
+        // Not a quick query: committing copies the container's filesystem
+        let output = Command::new(self.command())
+            .args(["commit", id, image])
+            .traced()
+            .output()
+            .context("Failed to commit container")?;
+        check(output, "preserve container state").map(drop)
+    }
+
+    fn image_exists(&self, image: &str) -> Result<bool> {
+        let status = Command::new(self.command())
+            .args(["image", "inspect", image])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .traced()
+            .timed_status()
+            .context("Failed to check for image")?;
+        Ok(status.success())
+    }
+
+    fn tag(&self, image: &str, tag: &str) -> Result<()> {
+        self.query(&["tag", image, tag], "tag image").map(drop)
+    }
+
+    fn rmi(&self, image: &str) -> Result<()> {
+        self.query(&["rmi", image], "remove image").map(drop)
+    }
+
+    fn volume_rm(&self, volume: &str) -> Result<()> {
+        self.query(&["volume", "rm", volume], "remove volume")
+            .map(drop)
+    }
+
+    fn copy_volume(&self, image: &str, from: &str, to: &str) -> Result<bool> {
+        if !self.volume_exists(from)? {
+            return Ok(false);
+        }
+
+        // Not a quick query: the whole home directory is copied
+        let output = Command::new(self.command())
+            .args([
+                "run",
+                "--rm",
+                "--user",
+                "root",
+                "-v",
+                &format!("{}:/from", from),
+                "-v",
+                &format!("{}:/to", to),
+                image,
+                "cp",
+                "-a",
+                "/from/.",
+                "/to/",
+            ])
+            .traced()
+            .output()
+            .context("Failed to copy home volume")?;
+        check(output, "copy home volume").map(|_| true)
+    }
+
+    fn transfer_image(&self, image: &str, to: Runtime) -> Result<()> {
+        pipe(
+            Command::new(self.command()).args(["save", image]),
+            Command::new(to.command()).arg("load"),
+        )
+        .context("Failed to copy image")
+    }
+
+    fn transfer_volume(&self, volume: &str, image: &str, to: Runtime) -> Result<bool> {
+        if !self.volume_exists(volume)? {
+            return Ok(false);
+        }
+
+        // Only the receiving side reads the archive from stdin
+        let run = |runtime: Runtime, flags: &[&str], mount: &str| {
+            let mut cmd = Command::new(runtime.command());
+            cmd.args(["run", "--rm", "--user", "root", "--entrypoint", "tar"])
+                .args(flags)
+                .arg("-v")
+                .arg(format!("{}:{}", volume, mount))
+                .arg(image);
+            cmd
+        };
+        pipe(
+            run(*self, &[], "/from").args(["-C", "/from", "-cf", "-", "."]),
+            run(to, &["-i"], "/to").args(["-C", "/to", "-xpf", "-"]),
+        )
+        .context("Failed to copy home volume")?;
+        Ok(true)
+    }
+
+    fn save_volume(&self, volume: &str, image: &str, file: &Path) -> Result<bool> {
+        if !self.volume_exists(volume)? {
+            return Ok(false);
+        }
+
+        let out = std::fs::File::create(file)
+            .with_context(|| format!("Failed to create {}", file.display()))?;
+        let output = Command::new(self.command())
+            .args(["run", "--rm", "--user", "root", "--entrypoint", "tar", "-v"])
+            .arg(format!("{}:/from:ro", volume))
+            .args([image, "-C", "/from", "-cf", "-", "."])
+            .stdout(out)
+            .traced()
+            .output()
+            .context("Failed to save home volume")?;
+        check(output, "save home volume").map(|_| true)
+    }
+
+    fn load_volume(&self, volume: &str, image: &str, file: &Path) -> Result<()> {
+        let input = std::fs::File::open(file)
+            .with_context(|| format!("Failed to open {}", file.display()))?;
+        let output = Command::new(self.command())
+            .args([
+                "run",
+                "--rm",
+                "-i",
+                "--user",
+                "root",
+                "--entrypoint",
+                "tar",
+                "-v",
+            ])
+            .arg(format!("{}:/to", volume))
+            .args([image, "-C", "/to", "-xpf", "-"])
+            .stdin(input)
+            .traced()
+            .output()
+            .context("Failed to restore home volume")?;
+        check(output, "restore home volume").map(drop)
+    }
+
+    fn build(&self, args: &[String], dockerfile: &str, log: &Path) -> Result<()> {
+        let (status, tail) = output::run_logged(
+            Command::new(self.command()).args(args),
+            Some(dockerfile.as_bytes()),
+            log,
+        )
+        .context("Failed to start image build")?;
+        if !status.success() {
+            bail!(
+                "Image build failed:\n  {}\nFull output in {}",
+                output::error_lines(&tail).join("\n  "),
+                log.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn exec(&self, args: &[String]) -> Result<bool> {
+        let status = Command::new(self.command())
+            .args(args)
+            .traced()
+            .status()
+            .context("Failed to run command in container")?;
+        Ok(status.success())
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FakeContainer {
+        pub id: String,
+        pub name: String,
+        pub labels: Vec<String>,
+        pub image: String,
+        pub running: bool,
+        /// Arguments the container was created with
+        pub args: Vec<String>,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct FakeState {
+        pub containers: Vec<FakeContainer>,
+        pub images: Vec<String>,
+        pub volumes: Vec<String>,
+        /// Make `run` fail, as when the new settings are rejected
+        pub fail_run: bool,
+        /// Make `exec` commands fail
+        pub fail_exec: bool,
+        /// Runtime reported by `kind`, Docker unless set
+        pub kind: Option<Runtime>,
+        /// Operations performed, e.g. "commit c1 img"
+        pub calls: Vec<String>,
+        next_id: usize,
+    }
+
+    /// A container runtime that only keeps state in memory
+    #[derive(Debug, Default)]
+    pub struct FakeRuntime {
+        pub state: RefCell<FakeState>,
+    }
+
+    impl FakeRuntime {
+        /// Add a container, returning its ID
+        pub fn add_container(&self, name: &str, labels: &[&str], running: bool) -> String {
+            let mut state = self.state.borrow_mut();
+            state.next_id += 1;
+            let id = format!("c{}", state.next_id);
+            state.containers.push(FakeContainer {
+                id: id.clone(),
+                name: name.to_string(),
+                labels: labels.iter().map(|l| l.to_string()).collect(),
+                image: "base".to_string(),
+                running,
+                args: Vec::new(),
+            });
+            id
+        }
+
+        pub fn container(&self, id: &str) -> Option<FakeContainer> {
+            let state = self.state.borrow();
+            state.containers.iter().find(|c| c.id == id).cloned()
+        }
+
+        fn update(&self, id: &str, call: String, f: impl FnOnce(&mut FakeContainer)) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(call);
+            match state.containers.iter_mut().find(|c| c.id == id) {
+                Some(container) => {
+                    f(container);
+                    Ok(())
+                }
+                None => bail!("No such container: {}", id),
+            }
+        }
+    }
+
+    /// Value following `flag` in command-line arguments
+    fn flag_values<'a>(args: &'a [String], flag: &'a str) -> impl Iterator<Item = String> + 'a {
+        args.windows(2)
+            .filter(move |pair| pair[0] == flag)
+            .map(|pair| pair[1].clone())
+    }
+
+    impl ContainerRuntime for FakeRuntime {
+        fn kind(&self) -> Runtime {
+            self.state.borrow().kind.unwrap_or(Runtime::Docker)
+        }
+
+        fn ps(&self, filter: &str, all: bool) -> Result<Vec<ContainerState>> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(format!("ps {}", filter));
+            let matches = |c: &FakeContainer| match filter.split_once('=') {
+                Some(("label", label)) => c.labels.iter().any(|l| l == label),
+                Some(("name", pattern)) => {
+                    pattern.trim_start_matches('^').trim_end_matches('$') == c.name
+                }
+                _ => false,
+            };
+            Ok(state
+                .containers
+                .iter()
+                .filter(|c| (all || c.running) && matches(c))
+                .map(|c| ContainerState {
+                    id: c.id.clone(),
+                    running: c.running,
+                })
+                .collect())
+        }
+
+        fn inspect(&self, id: &str) -> Result<Option<Inspected>> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(format!("inspect {}", id));
+            Ok(state
+                .containers
+                .iter()
+                .find(|c| c.id == id)
+                .map(|c| Inspected {
+                    running: c.running,
+                    name: c.name.clone(),
+                    image: c.image.clone(),
+                    labels: c
+                        .labels
+                        .iter()
+                        .filter_map(|l| l.split_once('='))
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                }))
+        }
+
+        fn run(&self, args: &[String]) -> Result<String> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push("run".to_string());
+            if state.fail_run {
+                bail!("Failed to create container: invalid settings");
+            }
+            let name = flag_values(args, "--name").next().unwrap_or_default();
+            if state.containers.iter().any(|c| c.name == name) {
+                bail!("Failed to create container: name {} is in use", name);
+            }
+            // Arguments end with the image and the shell
+            let image = args[args.len() - 2].clone();
+            state.next_id += 1;
+            let id = format!("c{}", state.next_id);
+            state.containers.push(FakeContainer {
+                id: id.clone(),
+                name,
+                labels: flag_values(args, "--label").collect(),
+                image,
+                running: true,
+                args: args.to_vec(),
+            });
+            Ok(id)
+        }
+
+        fn start(&self, id: &str) -> Result<()> {
+            self.update(id, format!("start {}", id), |c| c.running = true)
+        }
+
+        fn stop(&self, id: &str) -> Result<()> {
+            self.update(id, format!("stop {}", id), |c| c.running = false)
+        }
+
+        fn rm(&self, id: &str) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(format!("rm {}", id));
+            let before = state.containers.len();
+            state.containers.retain(|c| c.id != id);
+            if state.containers.len() == before {
+                bail!("No such container: {}", id);
+            }
+            Ok(())
+        }
+
+        fn rename(&self, id: &str, name: &str) -> Result<()> {
+            let name = name.to_string();
+            self.update(id, format!("rename {} {}", id, name), |c| c.name = name)
+        }
+
+        fn cp(&self, src: &Path, id: &str, dest: &str) -> Result<()> {
+            self.update(id, format!("cp {} {}:{}", src.display(), id, dest), |_| {})
+        }
+
+        fn commit(&self, id: &str, image: &str) -> Result<()> {
+            self.update(id, format!("commit {} {}", id, image), |_| {})?;
+            self.state.borrow_mut().images.push(image.to_string());
+            Ok(())
+        }
+
+        fn image_exists(&self, image: &str) -> Result<bool> {
+            Ok(self.state.borrow().images.iter().any(|i| i == image))
+        }
+
+        fn tag(&self, image: &str, tag: &str) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(format!("tag {} {}", image, tag));
+            if !state.images.iter().any(|i| i == image) {
+                bail!("No such image: {}", image);
+            }
+            state.images.push(tag.to_string());
+            Ok(())
+        }
+
+        fn rmi(&self, image: &str) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(format!("rmi {}", image));
+            let before = state.images.len();
+            state.images.retain(|i| i != image);
+            if state.images.len() == before {
+                bail!("No such image: {}", image);
+            }
+            Ok(())
+        }
+
+        fn volume_rm(&self, volume: &str) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(format!("volume rm {}", volume));
+            let before = state.volumes.len();
+            state.volumes.retain(|v| v != volume);
+            if state.volumes.len() == before {
+                bail!("No such volume: {}", volume);
+            }
+            Ok(())
+        }
+
+        fn copy_volume(&self, image: &str, from: &str, to: &str) -> Result<bool> {
+            let mut state = self.state.borrow_mut();
+            state
+                .calls
+                .push(format!("volume copy {} {} {}", image, from, to));
+            if !state.volumes.iter().any(|v| v == from) {
+                return Ok(false);
+            }
+            if state.fail_run {
+                bail!("Failed to copy home volume: invalid settings");
+            }
+            if !state.volumes.iter().any(|v| v == to) {
+                state.volumes.push(to.to_string());
+            }
+            Ok(true)
+        }
+
+        fn transfer_image(&self, image: &str, to: Runtime) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state
+                .calls
+                .push(format!("transfer image {} to {}", image, to));
+            if !state.images.iter().any(|i| i == image) {
+                bail!("No such image: {}", image);
+            }
+            Ok(())
+        }
+
+        fn transfer_volume(&self, volume: &str, image: &str, to: Runtime) -> Result<bool> {
+            let mut state = self.state.borrow_mut();
+            state
+                .calls
+                .push(format!("transfer volume {} {} to {}", volume, image, to));
+            if !state.images.iter().any(|i| i == image) {
+                bail!("No such image: {}", image);
+            }
+            Ok(state.volumes.iter().any(|v| v == volume))
+        }
+
+        fn save_volume(&self, volume: &str, image: &str, file: &Path) -> Result<bool> {
+            let mut state = self.state.borrow_mut();
+            state
+                .calls
+                .push(format!("save volume {} {}", volume, image));
+            if !state.volumes.iter().any(|v| v == volume) {
+                return Ok(false);
+            }
+            std::fs::write(file, volume)?;
+            Ok(true)
+        }
+
+        fn load_volume(&self, volume: &str, image: &str, file: &Path) -> Result<()> {
+            let mut state = self.state.borrow_mut();
+            state
+                .calls
+                .push(format!("load volume {} {}", volume, image));
+            if !file.is_file() {
+                bail!("No such file: {}", file.display());
+            }
+            if !state.volumes.iter().any(|v| v == volume) {
+                state.volumes.push(volume.to_string());
+            }
+            Ok(())
+        }
+
+        fn build(&self, args: &[String], _dockerfile: &str, _log: &Path) -> Result<()> {
+            let image = flag_values(args, "-t").next().context("No tag given")?;
+            self.state.borrow_mut().images.push(image);
+            Ok(())
+        }
+
+        fn exec(&self, args: &[String]) -> Result<bool> {
+            let mut state = self.state.borrow_mut();
+            state.calls.push(args.join(" "));
+            Ok(!state.fail_exec)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inspect() {
+        let inspected =
+            parse_inspect("true\t/jail-me-app-1a2b\tjail-base:full\t{\"jail.name\":\"me/app\"}\n")
+                .unwrap();
+        assert!(inspected.running);
+        assert_eq!(inspected.name, "jail-me-app-1a2b");
+        assert_eq!(inspected.image, "jail-base:full");
+        assert_eq!(inspected.labels["jail.name"], "me/app");
+
+        let unlabeled = parse_inspect("false\tadopted\tubuntu\tnull").unwrap();
+        assert!(!unlabeled.running);
+        assert!(unlabeled.labels.is_empty());
+
+        assert_eq!(parse_inspect(""), None);
+    }
+
+    #[test]
+    fn test_parse_ps() {
+        let containers = parse_ps("abc123\trunning\ndef456\texited\n789abc\tUp 5 minutes\n");
+        let running: Vec<bool> = containers.iter().map(|c| c.running).collect();
+        assert_eq!(running, [true, false, true]);
+        assert_eq!(containers[1].id, "def456");
+        assert!(parse_ps("").is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::port::PortMapping;
+
+/// Keys that carry no settings and are never reported as ignored
+const INFORMATIONAL_KEYS: [&str; 2] = ["name", "$schema"];
+
+/// The parts of a `devcontainer.json` that jail understands
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DevContainer {
+    pub image: Option<String>,
+    pub build: Option<Build>,
+    #[serde(default)]
+    pub forward_ports: Vec<Value>,
+    pub remote_user: Option<String>,
+    pub container_user: Option<String>,
+    #[serde(default)]
+    pub container_env: BTreeMap<String, String>,
+    pub post_create_command: Option<Value>,
+    /// Everything else, reported as ignored
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+/// `build` section: a Dockerfile relative to the devcontainer.json
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Build {
+    pub dockerfile: Option<String>,
+    pub context: Option<String>,
+    #[serde(default)]
+    pub args: BTreeMap<String, String>,
+}
+
+impl DevContainer {
+    /// Find the devcontainer.json of a workspace, if it has one
+    pub fn find(workspace: &Path) -> Option<PathBuf> {
+        [
+            workspace.join(".devcontainer").join("devcontainer.json"),
+            workspace.join(".devcontainer.json"),
+        ]
+        .into_iter()
+        .find(|path| path.is_file())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&strip_jsonc(&content))
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Top-level keys that are not supported
+    pub fn ignored_keys(&self) -> Vec<&str> {
+        self.other
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !INFORMATIONAL_KEYS.contains(key))
+            .collect()
+    }
+
+    /// Forwarded ports, plus any entries that can't be mapped (e.g. `"db:5432"`)
+    pub fn ports(&self) -> (Vec<PortMapping>, Vec<String>) {
+        let mut ports = Vec::new();
+        let mut unsupported = Vec::new();
+
+        for port in &self.forward_ports {
+            let spec = match port {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            // Only plain port numbers; "host:port" forwards from another host
+            match spec.parse::<u16>().map(|_| spec.parse()) {
+                Ok(Ok(mapping)) => ports.push(mapping),
+                _ => unsupported.push(spec),
+            }
+        }
+
+        (ports, unsupported)
+    }
+
+    /// User to run as inside the container
+    pub fn user(&self) -> Option<&str> {
+        self.remote_user
+            .as_deref()
+            .or(self.container_user.as_deref())
+    }
+
+    /// postCreateCommand as argument lists: a string runs through a shell, an
+    /// array runs directly, and an object's commands run one after another
+    pub fn post_create_commands(&self) -> Vec<Vec<String>> {
+        fn command(value: &Value) -> Option<Vec<String>> {
+            match value {
+                Value::String(s) => Some(vec!["/bin/sh".into(), "-c".into(), s.clone()]),
+                Value::Array(args) => args
+                    .iter()
+                    .map(|arg| arg.as_str().map(String::from))
+                    .collect(),
+                _ => None,
+            }
+        }
+
+        match &self.post_create_command {
+            Some(Value::Object(commands)) => commands.values().filter_map(command).collect(),
+            Some(value) => command(value).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Strip comments and trailing commas from JSON with comments (as used by devcontainer.json)
+fn strip_jsonc(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            ',' => {
+                // Drop the comma if only whitespace/comments separate it from a closing bracket
+                if !matches!(next_token(chars.clone()), Some('}' | ']')) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// First character that isn't whitespace or part of a comment
+fn next_token(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let mut comment: Option<char> = None;
+    let mut prev = ' ';
+
+    while let Some(c) = chars.next() {
+        match comment {
+            Some('/') if c == '\n' => comment = None,
+            Some('*') if prev == '*' && c == '/' => comment = None,
+            Some(_) => {}
+            None if c.is_whitespace() => {}
+            None if c == '/' => match chars.next() {
+                Some(kind @ ('/' | '*')) => comment = Some(kind),
+                _ => return Some(c),
+            },
+            None => return Some(c),
+        }
+        prev = c;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"{
+        // Comments are allowed
+        "name": "Example",
+        "build": { "dockerfile": "Dockerfile", "context": "..", },
+        "forwardPorts": [3000, "8080", "db:5432"],
+        "remoteUser": "vscode",
+        "containerEnv": { "URL": "http://localhost:3000/*path*/" },
+        /* block comment */
+        "postCreateCommand": "npm install",
+        "customizations": { "vscode": {} },
+    }"#;
+
+    #[test]
+    fn test_parse_devcontainer() {
+        let dc: DevContainer = serde_json::from_str(&strip_jsonc(EXAMPLE)).unwrap();
+        let build = dc.build.as_ref().unwrap();
+        assert_eq!(build.dockerfile.as_deref(), Some("Dockerfile"));
+        assert_eq!(build.context.as_deref(), Some(".."));
+        assert_eq!(dc.user(), Some("vscode"));
+        assert_eq!(dc.container_env["URL"], "http://localhost:3000/*path*/");
+        assert_eq!(dc.ignored_keys(), ["customizations"]);
+
+        let (ports, unsupported) = dc.ports();
+        assert_eq!(ports, ["3000".parse().unwrap(), "8080".parse().unwrap()]);
+        assert_eq!(unsupported, ["db:5432"]);
+
+        assert_eq!(
+            dc.post_create_commands(),
+            [["/bin/sh", "-c", "npm install"]]
+        );
+    }
+
+    #[test]
+    fn test_post_create_forms() {
+        let dc: DevContainer =
+            serde_json::from_str(r#"{"postCreateCommand": ["make", "setup"]}"#).unwrap();
+        assert_eq!(dc.post_create_commands(), [["make", "setup"]]);
+
+        let dc: DevContainer =
+            serde_json::from_str(r#"{"postCreateCommand": {"a": "one", "b": ["two"]}}"#).unwrap();
+        assert_eq!(
+            dc.post_create_commands(),
+            [vec!["/bin/sh", "-c", "one"], vec!["two"]]
+        );
+    }
+
+    #[test]
+    fn test_strip_jsonc() {
+        assert_eq!(
+            strip_jsonc(r#"{"a": "//x", "b": [1,],}"#),
+            r#"{"a": "//x", "b": [1]}"#
+        );
+        assert_eq!(strip_jsonc(r#"{"a": "\"//"} // c"#), r#"{"a": "\"//"} "#);
+        assert_eq!(strip_jsonc("[1, // c\n /* d */ ]"), "[1 \n  ]");
+    }
+}
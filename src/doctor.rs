@@ -0,0 +1,369 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config;
+use crate::container::ContainerRuntime;
+use crate::editor::Editor;
+use crate::image;
+use crate::jail::{self, JailMetadata};
+use crate::output::{done, Traced};
+use crate::process::Timed;
+use crate::runtime::{self, Availability, Runtime};
+
+/// Result of one check
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Pass(String),
+    /// Not applicable or not set up, which is fine
+    Skip(String),
+    /// A problem that doesn't stop jails from working, with a suggested fix
+    Warn(String, String),
+    /// A problem that breaks jails, with a suggested fix
+    Fail(String, String),
+}
+
+use Outcome::{Fail, Pass, Skip, Warn};
+
+/// Print one check, returning whether it failed
+fn report(name: &str, outcome: Outcome) -> bool {
+    let (mark, detail, fix) = match &outcome {
+        Pass(detail) => ("✓".green().bold(), detail, None),
+        Skip(detail) => ("-".dimmed(), detail, None),
+        Warn(problem, fix) => ("!".yellow().bold(), problem, Some(fix)),
+        Fail(problem, fix) => ("✗".red().bold(), problem, Some(fix)),
+    };
+    println!("  {} {}: {}", mark, name, detail);
+    if let Some(fix) = fix {
+        println!("      {}", fix.dimmed());
+    }
+    matches!(outcome, Fail(..))
+}
+
+/// Check the runtimes, images, host tools and jails, printing a line per check.
+/// Returns 1 if anything that breaks jails was found, 0 otherwise.
+pub fn run(requested: Option<Runtime>) -> Result<i32> {
+    let mut failed = 0;
+    let mut check = |name: &str, outcome| {
+        if report(name, outcome) {
+            failed += 1;
+        }
+    };
+
+    println!("{}", "Container runtimes".bold());
+    let mut available = Vec::new();
+    for runtime in [Runtime::Podman, Runtime::Docker] {
+        let outcome = check_runtime(runtime);
+        if matches!(outcome, Pass(_)) {
+            available.push(runtime);
+        }
+        check(runtime.command(), outcome);
+    }
+    if available.is_empty() {
+        check(
+            "runtime",
+            Fail(
+                "no container runtime is available".to_string(),
+                "Install Podman or Docker, or start the one installed (see 'jail status')"
+                    .to_string(),
+            ),
+        );
+    }
+    if cfg!(target_os = "macos") && which::which("podman").is_ok() {
+        check("podman machine", check_podman_machine());
+    }
+
+    // The runtime new jails get, without prompting to start anything
+    let active = match config::get_runtime_override(requested)? {
+        Some(runtime) => available.contains(&runtime).then_some(runtime),
+        None => available.first().copied(),
+    };
+
+    println!();
+    println!("{}", "Host".bold());
+    check("jails directory", check_jails_dir(&config::jails_dir()?));
+    check(
+        "git",
+        check_binary("git", "Install git and make sure it is on your PATH"),
+    );
+    check("editor", check_editor());
+    match active {
+        Some(runtime) => {
+            check("base image", check_base_image(runtime));
+            check("ssh agent", check_ssh_agent(runtime));
+        }
+        None => {
+            check("base image", Skip("no runtime to check with".to_string()));
+            check("ssh agent", Skip("no runtime to check with".to_string()));
+        }
+    }
+
+    let mut names = jail::get_jail_names()?;
+    names.sort();
+    if !names.is_empty() {
+        println!();
+        println!("{}", "Jails".bold());
+    }
+    for name in names {
+        check(&name, check_jail(&name, &available));
+    }
+
+    println!();
+    if failed > 0 {
+        println!(
+            "{} {} problem{} found",
+            "✗".red().bold(),
+            failed,
+            if failed == 1 { "" } else { "s" }
+        );
+        return Ok(1);
+    }
+    done!("No problems found");
+    Ok(0)
+}
+
+fn check_runtime(runtime: Runtime) -> Outcome {
+    match runtime.availability() {
+        Availability::Available => Pass(format!(
+            "responding ({})",
+            runtime
+                .version()
+                .unwrap_or_else(|| "unknown version".to_string())
+        )),
+        Availability::Missing => Skip("not installed".to_string()),
+        Availability::NotResponding(reason) => Warn(
+            format!("installed but not responding ({})", reason),
+            runtime.start_hint().to_string(),
+        ),
+    }
+}
+
+fn check_podman_machine() -> Outcome {
+    match runtime::podman_machine() {
+        Ok(Some((name, true))) => Pass(format!("'{}' is running", name)),
+        Ok(Some((name, false))) => Fail(
+            format!("'{}' is stopped", name),
+            format!("Start it with 'podman machine start {}'", name),
+        ),
+        Ok(None) => Fail(
+            "no machine exists".to_string(),
+            "Create and start one with 'podman machine init && podman machine start'".to_string(),
+        ),
+        Err(e) => Warn(
+            e.to_string(),
+            "Check your Podman installation with 'podman machine list'".to_string(),
+        ),
+    }
+}
+
+fn check_jails_dir(dir: &Path) -> Outcome {
+    let probe = dir.join(format!(".jail-doctor-{}", std::process::id()));
+    let writable = std::fs::create_dir_all(dir).and_then(|()| std::fs::write(&probe, ""));
+    let _ = std::fs::remove_file(&probe);
+    match writable {
+        Ok(()) => Pass(format!("{} is writable", dir.display())),
+        Err(e) => Fail(
+            format!("can't write to {}: {}", dir.display(), e),
+            format!("Fix the permissions of {}", dir.display()),
+        ),
+    }
+}
+
+fn check_binary(binary: &str, fix: &str) -> Outcome {
+    match which::which(binary) {
+        Ok(path) => Pass(path.display().to_string()),
+        Err(_) => Fail(format!("'{}' not found on PATH", binary), fix.to_string()),
+    }
+}
+
+fn check_editor() -> Outcome {
+    let configured = config::load().ok().and_then(|config| config.editor);
+    match Editor::resolve(configured.as_deref()) {
+        Ok(editor) => Pass(format!("{} (for 'jail code')", editor.name())),
+        Err(e) => Warn(
+            format!("{:#}", e),
+            "Install VSCode's 'code' command, or set editor in config.toml (only 'jail code' needs it)"
+                .to_string(),
+        ),
+    }
+}
+
+fn check_base_image(runtime: Runtime) -> Outcome {
+    base_image_outcome(runtime).unwrap_or_else(|e| {
+        Fail(
+            format!("can't check the base image: {:#}", e),
+            "Check that the runtime responds and the base image settings in 'jail config get'"
+                .to_string(),
+        )
+    })
+}
+
+fn base_image_outcome(runtime: Runtime) -> Result<Outcome> {
+    if let Some(base_image) = config::get_base_image_override()? {
+        return Ok(if image::exists(runtime, &base_image)? {
+            Pass(format!("{} (configured) is present", base_image))
+        } else {
+            Warn(
+                format!("{} (configured) is not pulled yet", base_image),
+                format!(
+                    "It is pulled on first use, or now with '{} pull {}'",
+                    runtime, base_image
+                ),
+            )
+        });
+    }
+
+    let preset = jail::resolve_preset(None)?;
+    let tag = image::image_tag(preset, None);
+    Ok(if !image::exists(runtime, &tag)? {
+        Warn(
+            format!("{} is not built yet", tag),
+            format!(
+                "It is built on first use, or now with 'jail image rebuild --preset {}'",
+                preset
+            ),
+        )
+    } else if image::is_stale(runtime, preset, None)? {
+        Warn(
+            format!("{} was built from an older Dockerfile", tag),
+            format!("Update it with 'jail image rebuild --preset {}'", preset),
+        )
+    } else {
+        Pass(format!("{} is up to date", tag))
+    })
+}
+
+fn check_ssh_agent(runtime: Runtime) -> Outcome {
+    let Some(args) = runtime.ssh_agent_mount(false) else {
+        return if runtime == Runtime::Podman && cfg!(target_os = "macos") {
+            Warn(
+                "Podman on macOS can't forward the SSH agent into jails".to_string(),
+                "Use HTTPS remotes inside jails, or Docker Desktop for SSH agent forwarding"
+                    .to_string(),
+            )
+        } else {
+            Warn(
+                "SSH_AUTH_SOCK is not set, so git over SSH won't work in jails".to_string(),
+                "Start an agent with 'eval $(ssh-agent)' and add your key with 'ssh-add'"
+                    .to_string(),
+            )
+        };
+    };
+
+    // The host side of the socket mount, when it is a path on this machine
+    let socket = args
+        .iter()
+        .find_map(|arg| arg.split_once(":/run/ssh.sock").map(|(host, _)| host))
+        .filter(|path| !path.starts_with("/run/host-services"));
+    match socket {
+        Some(path) if !is_socket(Path::new(path)) => Fail(
+            format!("SSH_AUTH_SOCK points to {}, which is not a socket", path),
+            "Restart your SSH agent and open a new terminal".to_string(),
+        ),
+        Some(path) => Pass(format!("{} is forwarded into jails", path)),
+        None => Pass(format!("forwarded by {}", runtime)),
+    }
+}
+
+#[cfg(unix)]
+fn is_socket(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket())
+}
+
+#[cfg(not(unix))]
+fn is_socket(path: &Path) -> bool {
+    path.exists()
+}
+
+fn check_jail(name: &str, available: &[Runtime]) -> Outcome {
+    let metadata = match jail::jail_path(name).and_then(|dir| JailMetadata::load(&dir)) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return Fail(
+                format!("{:#}", e),
+                "Run 'jail repair' to fix or remove it".to_string(),
+            )
+        }
+    };
+
+    let runtime = metadata.runtime;
+    if !available.contains(&runtime) {
+        let fix = match available.first() {
+            Some(other) => format!(
+                "{} Or move the jail with 'jail migrate {} --to {}'",
+                runtime.start_hint(),
+                name,
+                other
+            ),
+            None => runtime.start_hint().to_string(),
+        };
+        return Fail(format!("its runtime {} is not available", runtime), fix);
+    }
+
+    let container_id =
+        match jail::find_container_id(name, metadata.container_id.as_deref(), runtime) {
+            Ok(Some(id)) => id,
+            Ok(None) => return Pass(format!("{}, container is created on next use", runtime)),
+            Err(e) => return Warn(format!("{:#}", e), format!("Check '{} ps -a'", runtime)),
+        };
+    match container_image(runtime, &container_id) {
+        Some(image) if !runtime.image_exists(&image).unwrap_or(true) => Warn(
+            format!("the image of its container ({}) no longer exists", short_id(&image)),
+            format!(
+                "The container can't be recreated with new settings; save it with 'jail snapshot {}'",
+                name
+            ),
+        ),
+        _ => Pass(format!("{}, container {}", runtime, short_id(&container_id))),
+    }
+}
+
+/// ID of the image a container was created from
+fn container_image(runtime: Runtime, container_id: &str) -> Option<String> {
+    let output = Command::new(runtime.command())
+        .args([
+            "inspect",
+            "--type",
+            "container",
+            "-f",
+            "{{.Image}}",
+            container_id,
+        ])
+        .traced()
+        .timed_output()
+        .ok()?;
+    let image = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !image.is_empty()).then_some(image)
+}
+
+/// First 12 characters of an ID, without the digest algorithm
+fn short_id(id: &str) -> &str {
+    let id = id.strip_prefix("sha256:").unwrap_or(id);
+    &id[..id.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_id() {
+        assert_eq!(short_id("sha256:0123456789abcdef0123"), "0123456789ab");
+        assert_eq!(short_id("abc"), "abc");
+    }
+
+    #[test]
+    fn test_check_jails_dir() {
+        let dir = std::env::temp_dir().join(format!("jail-test-doctor-{}", std::process::id()));
+        assert!(matches!(check_jails_dir(&dir), Pass(_)));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A file where the directory should be
+        std::fs::write(&dir, "").unwrap();
+        assert!(matches!(check_jails_dir(&dir), Fail(..)));
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
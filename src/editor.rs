@@ -0,0 +1,145 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::process::Command;
+
+use crate::output::{warning, Traced};
+
+/// Editors that support the `vscode-remote://attached-container` URI scheme, in
+/// the order they're tried when none is configured
+const KNOWN_EDITORS: [(&str, &str); 4] = [
+    ("code", "VSCode"),
+    ("code-insiders", "VSCode Insiders"),
+    ("cursor", "Cursor"),
+    ("windsurf", "Windsurf"),
+];
+
+/// Placeholder replaced by the folder URI in custom editor commands
+const URI_PLACEHOLDER: &str = "{uri}";
+
+/// How to open a folder URI
+#[derive(Debug, PartialEq)]
+pub(crate) enum Editor {
+    /// A VSCode-compatible binary, invoked with `--folder-uri`
+    Binary(String),
+    /// A command template such as `myeditor --remote {uri}`
+    Template(String),
+}
+
+impl Editor {
+    /// Pick an editor from the `--editor` flag or the `editor` config key, falling
+    /// back to the first known editor on PATH when the preferred one is missing
+    pub fn resolve(preferred: Option<&str>) -> Result<Self> {
+        Self::resolve_with(preferred, |bin| which::which(bin).is_ok())
+    }
+
+    fn resolve_with(preferred: Option<&str>, on_path: impl Fn(&str) -> bool) -> Result<Self> {
+        let preferred = preferred.map(str::trim).filter(|e| !e.is_empty());
+
+        if let Some(editor) = preferred {
+            if editor.contains(URI_PLACEHOLDER) {
+                return Ok(Self::Template(editor.to_string()));
+            }
+            if on_path(editor) {
+                return Ok(Self::Binary(editor.to_string()));
+            }
+        }
+
+        let fallback = KNOWN_EDITORS
+            .iter()
+            .map(|(bin, _)| *bin)
+            .find(|bin| on_path(bin));
+
+        match (preferred, fallback) {
+            (Some(editor), Some(bin)) => {
+                warning!("'{}' not found on PATH, using '{}' instead", editor, bin);
+                Ok(Self::Binary(bin.to_string()))
+            }
+            (None, Some(bin)) => Ok(Self::Binary(bin.to_string())),
+            (Some(editor), None) => bail!(
+                "Editor '{}' not found on PATH, and no other supported editor is installed",
+                editor
+            ),
+            (None, None) => bail!(
+                "No supported editor found on PATH. Install one of: {}",
+                KNOWN_EDITORS.map(|(bin, _)| bin).join(", ")
+            ),
+        }
+    }
+
+    /// Name to show the user
+    pub fn name(&self) -> String {
+        match self {
+            Self::Binary(bin) => KNOWN_EDITORS
+                .iter()
+                .find(|(known, _)| known == bin)
+                .map_or_else(|| bin.clone(), |(_, name)| name.to_string()),
+            Self::Template(template) => template
+                .split_whitespace()
+                .next()
+                .unwrap_or(template)
+                .to_string(),
+        }
+    }
+
+    /// Program and arguments that open `uri`
+    fn command(&self, uri: &str) -> Vec<String> {
+        match self {
+            Self::Binary(bin) => vec![bin.clone(), "--folder-uri".into(), uri.into()],
+            Self::Template(template) => template
+                .split_whitespace()
+                .map(|arg| arg.replace(URI_PLACEHOLDER, uri))
+                .collect(),
+        }
+    }
+
+    /// Open `uri` in the editor
+    pub fn open(&self, uri: &str) -> Result<()> {
+        let command = self.command(uri);
+        let status = Command::new(&command[0])
+            .args(&command[1..])
+            .traced()
+            .status()
+            .with_context(|| format!("Failed to run '{}'", command[0]))?;
+
+        if !status.success() {
+            bail!("Failed to open {}", self.name());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_editor() {
+        let installed = |bin: &str| ["cursor", "windsurf"].contains(&bin);
+
+        let editor = Editor::resolve_with(Some("windsurf"), installed).unwrap();
+        assert_eq!(editor, Editor::Binary("windsurf".into()));
+        assert_eq!(editor.name(), "Windsurf");
+
+        // Falls back to the first installed editor
+        let editor = Editor::resolve_with(Some("code"), installed).unwrap();
+        assert_eq!(editor, Editor::Binary("cursor".into()));
+        let editor = Editor::resolve_with(None, installed).unwrap();
+        assert_eq!(editor, Editor::Binary("cursor".into()));
+
+        assert!(Editor::resolve_with(Some("code"), |_| false).is_err());
+        assert!(Editor::resolve_with(None, |_| false).is_err());
+    }
+
+    #[test]
+    fn test_editor_command() {
+        let uri = "vscode-remote://attached-container+ab/workspace";
+        assert_eq!(
+            Editor::Binary("code".into()).command(uri),
+            ["code", "--folder-uri", uri]
+        );
+
+        let editor = Editor::resolve_with(Some("myedit --remote {uri}"), |_| false).unwrap();
+        assert_eq!(editor.command(uri), ["myedit", "--remote", uri]);
+        assert_eq!(editor.name(), "myedit");
+    }
+}
@@ -0,0 +1,403 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::network::NetworkMode;
+use crate::runtime::{self, Runtime};
+
+/// Directory of the X server's sockets, mounted at the same path in containers
+const X11_SOCKETS: &str = "/tmp/.X11-unix";
+
+/// XDG_RUNTIME_DIR of GUI jails, where the Wayland socket is mounted
+const CONTAINER_RUNTIME_DIR: &str = "/tmp/jail-runtime";
+
+/// Where XQuartz, the X server for macOS, may be installed
+const XQUARTZ_PATHS: [&str; 2] = [
+    "/Applications/Utilities/XQuartz.app",
+    "/opt/X11/bin/Xquartz",
+];
+
+/// Host operating system, as far as display forwarding is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HostOs {
+    Linux,
+    MacOs,
+    Other,
+}
+
+impl HostOs {
+    pub fn current() -> Self {
+        if cfg!(target_os = "linux") {
+            HostOs::Linux
+        } else if cfg!(target_os = "macos") {
+            HostOs::MacOs
+        } else {
+            HostOs::Other
+        }
+    }
+}
+
+/// The host's display servers, from its environment
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct HostDisplay {
+    /// X11 display, e.g. `:0`
+    pub x11: Option<String>,
+    /// Path of the Wayland compositor's socket
+    pub wayland: Option<PathBuf>,
+    /// Whether XQuartz is installed
+    pub xquartz: bool,
+}
+
+impl HostDisplay {
+    pub fn detect() -> Self {
+        let var = |key| {
+            std::env::var(key)
+                .ok()
+                .filter(|value: &String| !value.is_empty())
+        };
+        Self {
+            x11: var("DISPLAY"),
+            wayland: var("WAYLAND_DISPLAY")
+                .and_then(|display| wayland_socket(&display, var("XDG_RUNTIME_DIR").as_deref())),
+            xquartz: XQUARTZ_PATHS.iter().any(|path| Path::new(path).exists()),
+        }
+    }
+}
+
+/// Path of the Wayland socket named by WAYLAND_DISPLAY, which is relative to
+/// XDG_RUNTIME_DIR unless absolute
+fn wayland_socket(display: &str, runtime_dir: Option<&str>) -> Option<PathBuf> {
+    let display = Path::new(display);
+    if display.is_absolute() {
+        Some(display.to_path_buf())
+    } else {
+        runtime_dir.map(|dir| Path::new(dir).join(display))
+    }
+}
+
+/// Runtime arguments and environment letting GUI apps in a container show
+/// windows on the host's display
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct GuiSetup {
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// Display forwarding for a `--gui` jail. Linux containers share the host's X11
+/// and Wayland sockets; on macOS they reach XQuartz over the network. `host_ipc`
+/// tells whether the container shares the host's IPC namespace (`--host-ipc`).
+pub(crate) fn setup(
+    host: &HostDisplay,
+    os: HostOs,
+    runtime: Runtime,
+    network: NetworkMode,
+    selinux: bool,
+    host_ipc: bool,
+) -> Result<GuiSetup> {
+    let mut setup = GuiSetup::default();
+    match os {
+        HostOs::Linux => {
+            if host.x11.is_none() && host.wayland.is_none() {
+                bail!(
+                    "--gui needs a display, but neither DISPLAY nor WAYLAND_DISPLAY is set. \
+                     Run jail from a graphical session."
+                );
+            }
+            // Relabeling the sockets would take them away from the host's display
+            // server, so the container is left unconfined by SELinux instead
+            if selinux {
+                setup.args.push("--security-opt".to_string());
+                setup.args.push("label=disable".to_string());
+            }
+            if let Some(display) = &host.x11 {
+                setup.args.push("-v".to_string());
+                setup
+                    .args
+                    .push(runtime::volume_arg(X11_SOCKETS, X11_SOCKETS, true, None));
+                setup.env.insert("DISPLAY".to_string(), display.clone());
+                // X11 apps pass images through shared memory (MIT-SHM), which fails
+                // without the host's IPC namespace. Qt doesn't fall back by itself.
+                if !host_ipc {
+                    setup
+                        .env
+                        .insert("QT_X11_NO_MITSHM".to_string(), "1".to_string());
+                }
+            }
+            if let Some(socket) = &host.wayland {
+                let name = socket
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "wayland-0".to_string());
+                setup.args.push("-v".to_string());
+                setup.args.push(runtime::volume_arg(
+                    &socket.to_string_lossy(),
+                    &format!("{}/{}", CONTAINER_RUNTIME_DIR, name),
+                    false,
+                    None,
+                ));
+                setup.env.insert("WAYLAND_DISPLAY".to_string(), name);
+                setup.env.insert(
+                    "XDG_RUNTIME_DIR".to_string(),
+                    CONTAINER_RUNTIME_DIR.to_string(),
+                );
+            }
+        }
+        HostOs::MacOs => {
+            if !host.xquartz {
+                bail!(
+                    "--gui on macOS needs XQuartz. Install it with 'brew install --cask xquartz', \
+                     enable \"Allow connections from network clients\" in its Security settings, \
+                     log out and back in, then run 'xhost +localhost'."
+                );
+            }
+            if network == NetworkMode::None {
+                bail!(
+                    "--gui on macOS reaches XQuartz over the network, which --network none blocks"
+                );
+            }
+            // Containers run in a VM, so X11 goes over TCP to XQuartz on the host
+            setup.env.insert(
+                "DISPLAY".to_string(),
+                format!("{}:0", runtime.host_gateway()),
+            );
+        }
+        HostOs::Other => bail!("--gui is only supported on Linux and macOS"),
+    }
+    Ok(setup)
+}
+
+/// What to do on the host if apps can't open the display, which X11 servers
+/// refuse to clients they don't know
+pub(crate) fn xhost_hint(host: &HostDisplay, os: HostOs) -> Option<&'static str> {
+    match os {
+        HostOs::Linux if host.x11.is_some() => Some("xhost +si:localuser:$USER"),
+        HostOs::MacOs => Some("xhost +localhost"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux_host(x11: Option<&str>, wayland: Option<&str>) -> HostDisplay {
+        HostDisplay {
+            x11: x11.map(String::from),
+            wayland: wayland.map(PathBuf::from),
+            xquartz: false,
+        }
+    }
+
+    #[test]
+    fn test_wayland_socket() {
+        assert_eq!(
+            wayland_socket("wayland-0", Some("/run/user/1000")),
+            Some(PathBuf::from("/run/user/1000/wayland-0"))
+        );
+        assert_eq!(
+            wayland_socket("/tmp/wayland-1", None),
+            Some(PathBuf::from("/tmp/wayland-1"))
+        );
+        assert_eq!(wayland_socket("wayland-0", None), None);
+    }
+
+    #[test]
+    fn test_linux_x11() {
+        let host = linux_host(Some(":0"), None);
+        let setup = setup(
+            &host,
+            HostOs::Linux,
+            Runtime::Docker,
+            NetworkMode::Host,
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(setup.args, ["-v", "/tmp/.X11-unix:/tmp/.X11-unix:ro"]);
+        assert_eq!(setup.env.get("DISPLAY").map(String::as_str), Some(":0"));
+        assert!(!setup.env.contains_key("WAYLAND_DISPLAY"));
+        // Without the host's IPC namespace, Qt is told not to use shared memory
+        assert_eq!(
+            setup.env.get("QT_X11_NO_MITSHM").map(String::as_str),
+            Some("1")
+        );
+
+        let shared = super::setup(
+            &host,
+            HostOs::Linux,
+            Runtime::Docker,
+            NetworkMode::Host,
+            false,
+            true,
+        )
+        .unwrap();
+        assert!(!shared.env.contains_key("QT_X11_NO_MITSHM"));
+
+        // The host's sockets are never relabeled
+        let selinux = super::setup(
+            &host,
+            HostOs::Linux,
+            Runtime::Podman,
+            NetworkMode::Host,
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            selinux.args,
+            [
+                "--security-opt",
+                "label=disable",
+                "-v",
+                "/tmp/.X11-unix:/tmp/.X11-unix:ro"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_linux_wayland() {
+        let host = linux_host(Some(":1"), Some("/run/user/1000/wayland-0"));
+        let setup = setup(
+            &host,
+            HostOs::Linux,
+            Runtime::Podman,
+            NetworkMode::Bridge,
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            setup.args,
+            [
+                "--security-opt",
+                "label=disable",
+                "-v",
+                "/tmp/.X11-unix:/tmp/.X11-unix:ro",
+                "-v",
+                "/run/user/1000/wayland-0:/tmp/jail-runtime/wayland-0",
+            ]
+        );
+        assert_eq!(setup.env.get("DISPLAY").map(String::as_str), Some(":1"));
+        assert_eq!(
+            setup.env.get("WAYLAND_DISPLAY").map(String::as_str),
+            Some("wayland-0")
+        );
+        assert_eq!(
+            setup.env.get("XDG_RUNTIME_DIR").map(String::as_str),
+            Some("/tmp/jail-runtime")
+        );
+
+        // Wayland alone needs no X11 socket
+        let host = linux_host(None, Some("/run/user/1000/wayland-0"));
+        let setup = super::setup(
+            &host,
+            HostOs::Linux,
+            Runtime::Docker,
+            NetworkMode::Host,
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(setup.args.len(), 2);
+        assert!(!setup.env.contains_key("DISPLAY"));
+        assert!(!setup.env.contains_key("QT_X11_NO_MITSHM"));
+    }
+
+    #[test]
+    fn test_linux_without_display() {
+        let host = linux_host(None, None);
+        let err = setup(
+            &host,
+            HostOs::Linux,
+            Runtime::Docker,
+            NetworkMode::Host,
+            false,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("DISPLAY"));
+    }
+
+    #[test]
+    fn test_macos() {
+        // Display variables of the host mean nothing to the VM
+        let mut host = linux_host(Some("/private/tmp/com.apple.launchd.x/org.xquartz:0"), None);
+        let err = setup(
+            &host,
+            HostOs::MacOs,
+            Runtime::Docker,
+            NetworkMode::Bridge,
+            false,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("brew install --cask xquartz"));
+
+        host.xquartz = true;
+        let docker = setup(
+            &host,
+            HostOs::MacOs,
+            Runtime::Docker,
+            NetworkMode::Bridge,
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(docker.args.is_empty());
+        assert_eq!(
+            docker.env.get("DISPLAY").map(String::as_str),
+            Some("host.docker.internal:0")
+        );
+        let podman = setup(
+            &host,
+            HostOs::MacOs,
+            Runtime::Podman,
+            NetworkMode::Bridge,
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            podman.env.get("DISPLAY").map(String::as_str),
+            Some("host.containers.internal:0")
+        );
+
+        assert!(setup(
+            &host,
+            HostOs::MacOs,
+            Runtime::Docker,
+            NetworkMode::None,
+            false,
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_other_os() {
+        let host = linux_host(Some(":0"), None);
+        assert!(setup(
+            &host,
+            HostOs::Other,
+            Runtime::Docker,
+            NetworkMode::Bridge,
+            false,
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_xhost_hint() {
+        assert!(xhost_hint(&linux_host(Some(":0"), None), HostOs::Linux).is_some());
+        assert!(xhost_hint(
+            &linux_host(None, Some("/run/user/1000/wayland-0")),
+            HostOs::Linux
+        )
+        .is_none());
+        assert_eq!(
+            xhost_hint(&HostDisplay::default(), HostOs::MacOs),
+            Some("xhost +localhost")
+        );
+    }
+}
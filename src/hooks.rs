@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::image;
+
+/// Hooks a repository defines for the jails cloned from it, relative to the workspace
+pub(crate) const REPO_HOOKS: &str = ".jail/hooks.toml";
+
+/// Commands run inside the container at points of a jail's life, each through
+/// `sh -c` in the workspace. Set in jail.toml's `[hooks]` table, or by the
+/// repository in `.jail/hooks.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Hooks {
+    /// Run once, after the container is first created
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_create: Vec<String>,
+    /// Run before each `jail enter` shell starts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_enter: Vec<String>,
+    /// Run after each `jail enter` shell exits
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_enter: Vec<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.post_create.is_empty() && self.pre_enter.is_empty() && self.post_enter.is_empty()
+    }
+
+    /// Add another set's commands after this one's
+    pub fn extend(&mut self, other: Hooks) {
+        self.post_create.extend(other.post_create);
+        self.pre_enter.extend(other.pre_enter);
+        self.post_enter.extend(other.post_enter);
+    }
+
+    /// Every command with the stage it runs at, for showing them to the user
+    pub fn commands(&self) -> impl Iterator<Item = (&'static str, &String)> {
+        self.post_create
+            .iter()
+            .map(|c| ("post_create", c))
+            .chain(self.pre_enter.iter().map(|c| ("pre_enter", c)))
+            .chain(self.post_enter.iter().map(|c| ("post_enter", c)))
+    }
+}
+
+/// Hooks defined by the repository in a workspace, with a hash of the file so a
+/// confirmation to run them is only valid for the content confirmed
+pub(crate) struct RepoHooks {
+    pub hooks: Hooks,
+    pub hash: String,
+}
+
+/// Read the hooks a repository defines in its workspace, if any
+pub(crate) fn load_repo_hooks(workspace: &Path) -> Result<Option<RepoHooks>> {
+    let path = workspace.join(REPO_HOOKS);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    let hooks: Hooks =
+        toml::from_str(&content).with_context(|| format!("Invalid {}", path.display()))?;
+    if hooks.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(RepoHooks {
+        hooks,
+        hash: image::content_hash(content.as_bytes()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_repo_hooks() {
+        let workspace =
+            std::env::temp_dir().join(format!("jail-test-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(workspace.join(".jail")).unwrap();
+        assert!(load_repo_hooks(&workspace).unwrap().is_none());
+
+        std::fs::write(
+            workspace.join(REPO_HOOKS),
+            "post_create = [\"npm install\"]\npre_enter = [\"docker compose up -d\"]\n",
+        )
+        .unwrap();
+        let first = load_repo_hooks(&workspace).unwrap().unwrap();
+        assert_eq!(first.hooks.post_create, ["npm install"]);
+        assert_eq!(first.hooks.pre_enter, ["docker compose up -d"]);
+        assert!(first.hooks.post_enter.is_empty());
+
+        // Changed hooks have to be confirmed again
+        std::fs::write(
+            workspace.join(REPO_HOOKS),
+            "post_create = [\"make setup\"]\n",
+        )
+        .unwrap();
+        let changed = load_repo_hooks(&workspace).unwrap().unwrap();
+        assert_ne!(changed.hash, first.hash);
+
+        std::fs::write(workspace.join(REPO_HOOKS), "post_crate = [\"typo\"]\n").unwrap();
+        assert!(load_repo_hooks(&workspace).is_err());
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -1,13 +1,188 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::io::Write;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::str::FromStr;
 
-use crate::runtime::Runtime;
+use crate::container::ContainerRuntime;
+use crate::jail;
+use crate::output::{self, done, step, warning, Traced};
+use crate::process::Timed;
+use crate::runtime::{self, Runtime};
 
-pub const IMAGE_NAME: &str = "jail-dev:latest";
+pub(crate) const IMAGE_NAME: &str = "jail-dev:latest";
 
-const DOCKERFILE: &str = r#"FROM ubuntu:24.04
+/// Image label holding the hash of the Dockerfile the image was built from
+const HASH_LABEL: &str = "io.jail.dockerfile-hash";
+
+/// Hash label of images built before labels were namespaced
+const LEGACY_HASH_LABEL: &str = "jail.dockerfile-hash";
+
+/// Image label holding the preset an image was built from
+const PRESET_LABEL: &str = "io.jail.preset";
+
+/// Image label holding the hash of the preset's Dockerfile alone, without the
+/// extras of whoever built it
+const BASE_HASH_LABEL: &str = "io.jail.base-dockerfile-hash";
+
+/// Toolchain selection for the embedded image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Node.js, Rust, Python and claude-code
+    #[default]
+    Full,
+    /// Node.js and claude-code
+    Node,
+    Rust,
+    Python,
+    /// Base tools only
+    Minimal,
+}
+
+impl Preset {
+    /// Dockerfile sections installed on top of the base
+    fn sections(&self) -> &'static [&'static str] {
+        match self {
+            Preset::Full => &[NODE, RUST, PYTHON],
+            Preset::Node => &[NODE],
+            Preset::Rust => &[RUST],
+            Preset::Python => &[PYTHON],
+            Preset::Minimal => &[],
+        }
+    }
+
+    /// Tag of the image built for this preset; `full` keeps the original name
+    pub fn image_name(&self) -> String {
+        match self {
+            Preset::Full => IMAGE_NAME.to_string(),
+            preset => format!("jail-dev-{}:latest", preset),
+        }
+    }
+
+    /// The complete Dockerfile for this preset
+    pub fn dockerfile(&self) -> String {
+        let mut dockerfile = BASE.to_string();
+        for section in self.sections() {
+            dockerfile.push_str(section);
+        }
+        dockerfile.push_str(FOOTER);
+        dockerfile
+    }
+}
+
+/// Container platform, for running another architecture under emulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Platform {
+    #[serde(rename = "linux/amd64")]
+    #[value(name = "linux/amd64")]
+    Amd64,
+    #[serde(rename = "linux/arm64")]
+    #[value(name = "linux/arm64")]
+    Arm64,
+}
+
+impl Platform {
+    /// Architecture as reported by `image inspect`
+    pub fn arch(&self) -> &'static str {
+        match self {
+            Platform::Amd64 => "amd64",
+            Platform::Arm64 => "arm64",
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "linux/{}", self.arch())
+    }
+}
+
+/// `--platform` arguments for build, pull and run
+pub(crate) fn platform_args(platform: Option<Platform>) -> Vec<String> {
+    platform
+        .map(|platform| vec!["--platform".to_string(), platform.to_string()])
+        .unwrap_or_default()
+}
+
+/// Tag of the image built for a preset and platform, e.g. `jail-dev:latest-amd64`.
+/// Jails without a platform use the native image.
+pub(crate) fn image_tag(preset: Preset, platform: Option<Platform>) -> String {
+    match platform {
+        Some(platform) => format!("{}-{}", preset.image_name(), platform.arch()),
+        None => preset.image_name(),
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Preset::Full => write!(f, "full"),
+            Preset::Node => write!(f, "node"),
+            Preset::Rust => write!(f, "rust"),
+            Preset::Python => write!(f, "python"),
+            Preset::Minimal => write!(f, "minimal"),
+        }
+    }
+}
+
+/// An apt package installed into the base image (`extra_packages` in config),
+/// e.g. `jq` or `postgresql-client=16+257`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Package(String);
+
+impl FromStr for Package {
+    type Err = String;
+
+    /// Only characters of Debian package names and versions, starting with a
+    /// letter or digit, since names end up in a shell command of the Dockerfile
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = s.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.:=~".contains(c));
+        if !valid {
+            return Err(format!(
+                "invalid package name '{}': use lowercase letters, digits and + - . : = ~",
+                s
+            ));
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl fmt::Display for Package {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Package {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Package {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Configured extra packages, sorted and without duplicates so their order
+/// doesn't make the image look out of date
+pub(crate) fn extra_packages() -> Result<Vec<Package>> {
+    let mut packages = crate::config::load()?.extra_packages.unwrap_or_default();
+    packages.sort();
+    packages.dedup();
+    Ok(packages)
+}
+
+const BASE: &str = r#"FROM ubuntu:24.04
 
 # Avoid interactive prompts
 ENV DEBIAN_FRONTEND=noninteractive
@@ -22,6 +197,12 @@ RUN apt-get update && apt-get install -y \
     vim \
     openssh-client \
     ca-certificates \
+    gnupg \
+    zsh \
+    fish \
+    tmux \
+    tzdata \
+    locales \
     # VSCode Server dependencies
     libxkbfile1 \
     libsecret-1-0 \
@@ -34,14 +215,47 @@ RUN apt-get update && apt-get install -y \
     libasound2t64 \
     && rm -rf /var/lib/apt/lists/*
 
-# Create non-root user with sudo access
-RUN useradd -m -s /bin/bash dev && \
+# UTF-8 locales for LANG/LC_ALL forwarded from the host (C.UTF-8 is built in)
+RUN locale-gen en_US.UTF-8
+
+# docker CLI and Compose plugin, which talk to the host's Docker or Podman in jails
+# created with --docker-socket. Only with docker_cli in config.toml. They come from
+# Docker's apt repository, whose signing key must have the pinned fingerprint; apt
+# then checks each package's SHA-256 against the signed index.
+ARG DOCKER_CLI=""
+RUN if [ -n "$DOCKER_CLI" ]; then \
+        curl -fsSL -o /tmp/docker.asc https://download.docker.com/linux/ubuntu/gpg && \
+        gpg --show-keys --with-colons /tmp/docker.asc \
+            | grep -qx 'fpr:::::::::9DC858229FC7DD38854AE2D88D81803C0EBFCD88:' && \
+        install -D -m 0644 /tmp/docker.asc /etc/apt/keyrings/docker.asc && \
+        rm /tmp/docker.asc && \
+        echo "deb [arch=$(dpkg --print-architecture) signed-by=/etc/apt/keyrings/docker.asc] https://download.docker.com/linux/ubuntu noble stable" \
+            > /etc/apt/sources.list.d/docker.list && \
+        apt-get update && apt-get install -y docker-ce-cli docker-compose-plugin && \
+        rm -rf /var/lib/apt/lists/*; \
+    fi
+
+# Create non-root user with sudo access. Its IDs are the host user's on Linux, so
+# files it creates in the bind-mounted workspace belong to them on the host.
+ARG USER_UID=1000
+ARG USER_GID=1000
+RUN (userdel -r ubuntu 2>/dev/null || true) && \
+    groupadd -o -g "$USER_GID" dev && \
+    useradd -m -o -u "$USER_UID" -g dev -s /bin/bash dev && \
     echo "dev ALL=(ALL) NOPASSWD:ALL" >> /etc/sudoers
 
+# Packages from extra_packages in config.toml, late so changing them rebuilds little
+ARG EXTRA_PACKAGES=""
+RUN if [ -n "$EXTRA_PACKAGES" ]; then \
+        apt-get update && apt-get install -y $EXTRA_PACKAGES && rm -rf /var/lib/apt/lists/*; \
+    fi
+
 # Switch to dev user for tool installations
 USER dev
 WORKDIR /home/dev
+"#;
 
+const NODE: &str = r#"
 # Install nvm and Node.js
 ENV NVM_DIR=/home/dev/.nvm
 RUN curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.40.1/install.sh | bash && \
@@ -49,23 +263,36 @@ RUN curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.40.1/install.sh | b
     nvm install --lts && \
     nvm use --lts
 
+# Install claude-code globally via npm
+RUN . "$NVM_DIR/nvm.sh" && npm install -g @anthropic-ai/claude-code
+
+# Setup bash profile to load nvm
+RUN echo 'export NVM_DIR="$HOME/.nvm"' >> ~/.bashrc && \
+    echo '[ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"' >> ~/.bashrc && \
+    echo '[ -s "$NVM_DIR/bash_completion" ] && \. "$NVM_DIR/bash_completion"' >> ~/.bashrc
+
+# Mirror the nvm setup for zsh and fish (nvm itself is bash/zsh only, so fish just gets node on PATH)
+RUN echo 'export NVM_DIR="$HOME/.nvm"' >> ~/.zshrc && \
+    echo '[ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"' >> ~/.zshrc && \
+    mkdir -p ~/.config/fish/conf.d && \
+    echo 'set -gx NVM_DIR $HOME/.nvm' > ~/.config/fish/conf.d/nvm.fish && \
+    echo 'for dir in $NVM_DIR/versions/node/*/bin; fish_add_path -g $dir; end' >> ~/.config/fish/conf.d/nvm.fish
+"#;
+
+const RUST: &str = r#"
 # Install Rust via rustup
 RUN curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh -s -- -y
 ENV PATH="/home/dev/.cargo/bin:${PATH}"
+"#;
 
+const PYTHON: &str = r#"
 # Install Python3 (already in ubuntu, just ensure pip)
 USER root
 RUN apt-get update && apt-get install -y python3-pip python3-venv && rm -rf /var/lib/apt/lists/*
 USER dev
+"#;
 
-# Install claude-code globally via npm
-RUN . "$NVM_DIR/nvm.sh" && npm install -g @anthropic-ai/claude-code
-
-# Setup bash profile to load nvm
-RUN echo 'export NVM_DIR="$HOME/.nvm"' >> ~/.bashrc && \
-    echo '[ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"' >> ~/.bashrc && \
-    echo '[ -s "$NVM_DIR/bash_completion" ] && \. "$NVM_DIR/bash_completion"' >> ~/.bashrc
-
+const FOOTER: &str = r#"
 # Set working directory
 WORKDIR /workspace
 
@@ -73,60 +300,466 @@ WORKDIR /workspace
 CMD ["/bin/bash"]
 "#;
 
-/// Check if the jail-dev image exists
-pub fn exists(runtime: Runtime) -> Result<bool> {
+/// Image new containers are created from: the configured override, or the preset's built image
+pub(crate) fn base_image(preset: Preset, platform: Option<Platform>) -> Result<String> {
+    Ok(crate::config::get_base_image_override()?.unwrap_or_else(|| image_tag(preset, platform)))
+}
+
+/// Output of image builds and pulls in quiet mode
+pub(crate) fn build_log() -> Result<PathBuf> {
+    Ok(crate::config::data_dir()?.join("logs").join("build.log"))
+}
+
+/// Check if an image is present locally
+pub(crate) fn exists(runtime: Runtime, image: &str) -> Result<bool> {
+    runtime.image_exists(image)
+}
+
+/// Check if an image is present locally for the given platform. An image of
+/// another architecture counts as missing, so it gets built or pulled rather
+/// than silently run under emulation.
+pub(crate) fn exists_for(
+    runtime: Runtime,
+    image: &str,
+    platform: Option<Platform>,
+) -> Result<bool> {
+    let Some(platform) = platform else {
+        return exists(runtime, image);
+    };
+
     let output = Command::new(runtime.command())
-        .args(["image", "inspect", IMAGE_NAME])
-        .stdout(Stdio::null())
+        .args(["image", "inspect", "-f", "{{.Architecture}}", image])
         .stderr(Stdio::null())
-        .status()
+        .traced()
+        .timed_output()
         .context("Failed to check for image")?;
 
-    Ok(output.success())
+    Ok(
+        output.status.success()
+            && String::from_utf8_lossy(&output.stdout).trim() == platform.arch(),
+    )
+}
+
+/// What config.toml adds to the built base images, as build arguments
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct Extras {
+    /// apt packages (`extra_packages`)
+    pub packages: Vec<Package>,
+    /// Whether the docker CLI and Compose plugin are installed (`docker_cli`)
+    pub docker_cli: bool,
+}
+
+impl Extras {
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            packages: extra_packages()?,
+            docker_cli: crate::config::load()?.docker_cli.unwrap_or(false),
+        })
+    }
 }
 
-/// Build the jail-dev image
-pub fn build(runtime: Runtime) -> Result<()> {
-    println!(
-        "{} Building {} image (one-time setup, may take a few minutes)...",
-        "→".blue().bold(),
-        IMAGE_NAME.cyan()
+/// Hash a preset's Dockerfile and the extras it's built with. Without any,
+/// it's the hash of the Dockerfile alone.
+fn dockerfile_hash(preset: Preset, extras: &Extras) -> String {
+    let mut content = preset.dockerfile();
+    if !extras.packages.is_empty() {
+        content.push_str(&format!(
+            "\nEXTRA_PACKAGES={}",
+            join_packages(&extras.packages)
+        ));
+    }
+    if extras.docker_cli {
+        content.push_str("\nDOCKER_CLI=1");
+    }
+    content_hash(content.as_bytes())
+}
+
+/// Packages as the EXTRA_PACKAGES build argument
+fn join_packages(packages: &[Package]) -> String {
+    packages
+        .iter()
+        .map(|p| p.0.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// FNV-1a hash of some content as hex, stable across builds and Rust versions
+pub(crate) fn content_hash(content: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in content {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Dockerfile hashes an image is labeled with, none if it wasn't built by jail
+fn hash_labels(runtime: Runtime, image: &str) -> Result<Vec<String>> {
+    let output = Command::new(runtime.command())
+        .args([
+            "image",
+            "inspect",
+            "-f",
+            &format!(
+                "{{{{index .Config.Labels \"{}\"}}}} {{{{index .Config.Labels \"{}\"}}}}",
+                HASH_LABEL, LEGACY_HASH_LABEL
+            ),
+            image,
+        ])
+        .traced()
+        .timed_output()
+        .context("Failed to inspect image")?;
+    Ok(parse_hash_labels(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Hashes among label values from `inspect`, which docker shows as `<no value>`
+/// when missing
+fn parse_hash_labels(output: &str) -> Vec<String> {
+    output
+        .split_whitespace()
+        .filter(|label| label.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(String::from)
+        .collect()
+}
+
+/// Check if a preset's existing image was built from an older Dockerfile or other
+/// extra packages
+pub(crate) fn is_stale(
+    runtime: Runtime,
+    preset: Preset,
+    platform: Option<Platform>,
+) -> Result<bool> {
+    // Images without either label predate staleness detection
+    let hash = dockerfile_hash(preset, &Extras::load()?);
+    Ok(!hash_labels(runtime, &image_tag(preset, platform))?.contains(&hash))
+}
+
+/// Check if a configured base image, pushed with `jail image push`, was built from
+/// another Dockerfile than its preset's in this version of jail. The extras it was
+/// built with are the pusher's, so they don't count. Images built some other way,
+/// or by versions of jail that didn't label the preset, never are.
+fn is_stale_remote(runtime: Runtime, image: &str) -> Result<bool> {
+    let output = Command::new(runtime.command())
+        .args([
+            "image",
+            "inspect",
+            "-f",
+            &format!(
+                "{{{{index .Config.Labels \"{}\"}}}} {{{{index .Config.Labels \"{}\"}}}}",
+                PRESET_LABEL, BASE_HASH_LABEL
+            ),
+            image,
+        ])
+        .traced()
+        .timed_output()
+        .context("Failed to inspect image")?;
+    Ok(
+        match parse_base_labels(&String::from_utf8_lossy(&output.stdout)) {
+            Some((preset, hash)) => hash != dockerfile_hash(preset, &Extras::default()),
+            None => false,
+        },
+    )
+}
+
+/// Preset and base Dockerfile hash from `inspect`, if the image has both labels
+fn parse_base_labels(output: &str) -> Option<(Preset, String)> {
+    let mut labels = output.split_whitespace();
+    let preset = <Preset as clap::ValueEnum>::from_str(labels.next()?, false).ok()?;
+    let hash = labels.next()?;
+    hash.chars()
+        .all(|c| c.is_ascii_hexdigit())
+        .then(|| (preset, hash.to_string()))
+}
+
+/// Build the jail-dev image for a preset, for another platform if given
+pub fn build(
+    runtime: Runtime,
+    preset: Preset,
+    platform: Option<Platform>,
+    no_cache: bool,
+) -> Result<()> {
+    let image = image_tag(preset, platform);
+    step!(
+        "Building {} image (one-time setup, may take a few minutes)...",
+        image.cyan()
     );
-    println!("  This only happens once. Future jails will start instantly.");
+    if !output::is_quiet() {
+        println!("  This only happens once. Future jails will start instantly.");
+    }
+
+    // Give the dev user the host user's IDs; root keeps the image defaults
+    let host_ids = runtime::host_ids().filter(|&(uid, _)| uid != 0);
+    build_image(runtime, &image, preset, platform, no_cache, host_ids)?;
+
+    done!("Image {} built successfully", image.cyan());
+
+    Ok(())
+}
+
+/// Build a preset's image as `image`, with the configured extras
+fn build_image(
+    runtime: Runtime,
+    image: &str,
+    preset: Preset,
+    platform: Option<Platform>,
+    no_cache: bool,
+    host_ids: Option<(u32, u32)>,
+) -> Result<()> {
+    // An empty context: the Dockerfile copies nothing in, and sending the current
+    // directory could upload gigabytes to the daemon
+    let context = std::env::temp_dir().join(format!("jail-build-{}", std::process::id()));
+    std::fs::create_dir_all(&context)
+        .with_context(|| format!("Failed to create {}", context.display()))?;
+    let args = build_args(
+        image,
+        preset,
+        platform,
+        no_cache,
+        host_ids,
+        &Extras::load()?,
+        &context,
+    );
+    let built = runtime.build(&args, &preset.dockerfile(), &build_log()?);
+    let _ = std::fs::remove_dir_all(&context);
+    built
+}
+
+/// Arguments of `build` for a preset's image, reading the Dockerfile from stdin
+/// and building in `context`. `host_ids` become the dev user's UID/GID.
+fn build_args(
+    image: &str,
+    preset: Preset,
+    platform: Option<Platform>,
+    no_cache: bool,
+    host_ids: Option<(u32, u32)>,
+    extras: &Extras,
+    context: &Path,
+) -> Vec<String> {
+    let label = format!("{}={}", HASH_LABEL, dockerfile_hash(preset, extras));
+    let preset_label = format!("{}={}", PRESET_LABEL, preset);
+    let base_label = format!(
+        "{}={}",
+        BASE_HASH_LABEL,
+        dockerfile_hash(preset, &Extras::default())
+    );
+    let mut args: Vec<String> = [
+        "build",
+        "-t",
+        image,
+        "--label",
+        &label,
+        "--label",
+        &preset_label,
+        "--label",
+        &base_label,
+        "--label",
+        jail::CREATED_BY_LABEL,
+    ]
+    .map(String::from)
+    .to_vec();
+    args.extend(platform_args(platform));
+    if no_cache {
+        args.push("--no-cache".to_string());
+    }
+    if let Some((uid, gid)) = host_ids {
+        args.extend([
+            "--build-arg".to_string(),
+            format!("USER_UID={}", uid),
+            "--build-arg".to_string(),
+            format!("USER_GID={}", gid),
+        ]);
+    }
+    if !extras.packages.is_empty() {
+        args.extend([
+            "--build-arg".to_string(),
+            format!("EXTRA_PACKAGES={}", join_packages(&extras.packages)),
+        ]);
+    }
+    if extras.docker_cli {
+        args.extend(["--build-arg".to_string(), "DOCKER_CLI=1".to_string()]);
+    }
+    args.extend(["-f".to_string(), "-".to_string()]);
+    args.push(context.display().to_string());
+    args
+}
 
-    let mut child = Command::new(runtime.command())
-        .args(["build", "-t", IMAGE_NAME, "-f", "-", "."])
-        .stdin(Stdio::piped())
-        .spawn()
-        .context("Failed to start image build")?;
+/// Pull an image from its registry, for another platform if given
+pub(crate) fn pull(runtime: Runtime, image: &str, platform: Option<Platform>) -> Result<()> {
+    step!("Pulling image {}...", image.cyan());
 
-    // Write Dockerfile to stdin
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(DOCKERFILE.as_bytes())
-            .context("Failed to write Dockerfile")?;
+    let status = output::run(
+        Command::new(runtime.command())
+            .arg("pull")
+            .args(platform_args(platform))
+            .arg(image),
+        &build_log()?,
+    )?;
+
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to pull image '{}'. Check the image name and that you are logged in to the registry.",
+            image
+        );
     }
 
-    let status = child.wait().context("Failed to wait for build")?;
+    done!("Image {} pulled", image.cyan());
+
+    Ok(())
+}
+
+/// Registry an image reference points at, none for Docker Hub
+fn registry(image: &str) -> Option<&str> {
+    let (first, _) = image.split_once('/')?;
+    (first.contains('.') || first.contains(':') || first == "localhost").then_some(first)
+}
 
+/// Command logging in to the registry of an image
+fn login_hint(runtime: Runtime, image: &str) -> String {
+    match registry(image) {
+        Some(registry) => format!("{} login {}", runtime.command(), registry),
+        None => format!("{} login", runtime.command()),
+    }
+}
+
+/// Give an image another name
+fn tag(runtime: Runtime, source: &str, target: &str) -> Result<()> {
+    let status = Command::new(runtime.command())
+        .args(["tag", source, target])
+        .traced()
+        .timed_status()
+        .context("Failed to tag image")?;
     if !status.success() {
-        anyhow::bail!("Image build failed");
+        anyhow::bail!("Failed to tag image '{}' as '{}'", source, target);
     }
+    Ok(())
+}
 
-    println!(
-        "{} Image {} built successfully",
-        "✓".green().bold(),
-        IMAGE_NAME.cyan()
+/// Build a preset's image as `target` and push it to a registry, for teammates to
+/// set as their base_image. It's built for them rather than taken from the local
+/// image, whose dev user has this host's UID and GID. Its preset and Dockerfile
+/// labels go with it, so their jail can tell when it's out of date.
+pub fn push(
+    runtime: Runtime,
+    target: &str,
+    preset: Preset,
+    platform: Option<Platform>,
+) -> Result<()> {
+    step!(
+        "Building {} from the {} preset, with the image's default user IDs...",
+        target.cyan(),
+        preset
     );
+    build_image(runtime, target, preset, platform, false, None)?;
+
+    step!("Pushing image {}...", target.cyan());
+    let status = output::run(
+        Command::new(runtime.command()).args(["push", target]),
+        &build_log()?,
+    )?;
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to push image '{}'. Check that you are logged in to the registry ('{}').",
+            target,
+            login_hint(runtime, target)
+        );
+    }
 
+    done!("Image {} pushed", target.cyan());
+    if !output::is_quiet() {
+        println!(
+            "  Teammates can use it with '{}'",
+            format!("jail config set base_image {}", target).cyan()
+        );
+    }
     Ok(())
 }
 
-/// Ensure the jail-dev image exists, building if necessary
-pub fn ensure(runtime: Runtime) -> Result<()> {
-    if !exists(runtime)? {
-        build(runtime)?;
+/// Pull a base image, or when that fails and `build_if_missing` is set, build the
+/// preset's image and give it the base image's name
+pub fn pull_or_build(
+    runtime: Runtime,
+    image: &str,
+    preset: Preset,
+    platform: Option<Platform>,
+    build_if_missing: bool,
+) -> Result<()> {
+    let Err(e) = pull(runtime, image, platform) else {
+        return Ok(());
+    };
+    if !build_if_missing {
+        return Err(e.context(format!(
+            "Log in with '{}', or set build_if_missing = true to build the image locally instead",
+            login_hint(runtime, image)
+        )));
+    }
+
+    warning!("{:#}. Building it locally instead.", e);
+    build(runtime, preset, platform, false)?;
+    tag(runtime, &image_tag(preset, platform), image)
+}
+
+/// Ensure the base image exists, pulling a configured image or building the preset's image if necessary
+pub(crate) fn ensure(runtime: Runtime, preset: Preset, platform: Option<Platform>) -> Result<()> {
+    if let Some(image) = crate::config::get_base_image_override()? {
+        if !exists_for(runtime, &image, platform)? {
+            let build_if_missing = crate::config::build_if_missing()?;
+            return pull_or_build(runtime, &image, preset, platform, build_if_missing);
+        }
+        if is_stale_remote(runtime, &image)? {
+            warning!(
+                "Base image {} was built from another Dockerfile than this version of jail's. \
+                 Pull a newer one with '{}'.",
+                image.cyan(),
+                "jail image pull".cyan()
+            );
+        }
+        return Ok(());
     }
+
+    if !exists_for(runtime, &image_tag(preset, platform), platform)? {
+        return build(runtime, preset, platform, false);
+    }
+
+    if is_stale(runtime, preset, platform)? {
+        // Never block scripts on a prompt
+        if !crate::jail::is_interactive() {
+            warning!(
+                "Base image is out of date. Run '{}' to update it.",
+                format!("jail image rebuild --preset {}", preset).cyan()
+            );
+            return Ok(());
+        }
+
+        let rebuild = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Base image is out of date, rebuild now?")
+            .default(false)
+            .interact()?;
+
+        if rebuild {
+            build(runtime, preset, platform, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the embedded Dockerfile for a preset
+pub fn show(preset: Preset) {
+    print!("{}", preset.dockerfile());
+}
+
+/// Remove a preset's jail-dev image
+pub fn remove(runtime: Runtime, preset: Preset, platform: Option<Platform>) -> Result<()> {
+    let image = image_tag(preset, platform);
+    if !exists(runtime, &image)? {
+        println!("Image {} does not exist.", image.cyan());
+        return Ok(());
+    }
+
+    runtime.rmi(&image)?;
+
+    done!("Image {} removed", image.cyan());
+
     Ok(())
 }
 
@@ -141,8 +774,167 @@ mod tests {
 
     #[test]
     fn test_dockerfile_not_empty() {
-        assert!(!DOCKERFILE.is_empty());
-        assert!(DOCKERFILE.contains("ubuntu:24.04"));
-        assert!(DOCKERFILE.contains("dev"));
+        let dockerfile = Preset::Full.dockerfile();
+        assert!(dockerfile.contains("ubuntu:24.04"));
+        assert!(dockerfile.contains("dev"));
+        assert!(dockerfile.contains("rustup") && dockerfile.contains("nvm"));
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(Preset::default().image_name(), IMAGE_NAME);
+        assert_eq!(Preset::Rust.image_name(), "jail-dev-rust:latest");
+
+        let python = Preset::Python.dockerfile();
+        assert!(python.contains("python3-pip"));
+        assert!(!python.contains("nvm") && !python.contains("rustup"));
+
+        let minimal = Preset::Minimal.dockerfile();
+        assert!(minimal.contains("useradd -m -o -u \"$USER_UID\" -g dev -s /bin/bash dev"));
+        assert!(minimal.ends_with(FOOTER));
+        assert!(minimal.contains("tmux"));
+    }
+
+    #[test]
+    fn test_platform_image_tag() {
+        assert_eq!(image_tag(Preset::Full, None), IMAGE_NAME);
+        assert_eq!(
+            image_tag(Preset::Full, Some(Platform::Amd64)),
+            "jail-dev:latest-amd64"
+        );
+        assert_eq!(
+            image_tag(Preset::Rust, Some(Platform::Arm64)),
+            "jail-dev-rust:latest-arm64"
+        );
+        assert_eq!(
+            platform_args(Some(Platform::Amd64)),
+            ["--platform", "linux/amd64"]
+        );
+        assert!(platform_args(None).is_empty());
+    }
+
+    #[test]
+    fn test_dockerfile_hash_stable() {
+        let none = Extras::default();
+        let full = dockerfile_hash(Preset::Full, &none);
+        assert_eq!(full, dockerfile_hash(Preset::Full, &none));
+        assert_eq!(full, content_hash(Preset::Full.dockerfile().as_bytes()));
+        assert_eq!(full.len(), 16);
+        assert_ne!(full, dockerfile_hash(Preset::Node, &none));
+
+        // Extra packages and the docker CLI make a different image
+        let jq = Extras {
+            packages: vec!["jq".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_ne!(dockerfile_hash(Preset::Full, &jq), full);
+        let docker_cli = Extras {
+            docker_cli: true,
+            ..Default::default()
+        };
+        assert_ne!(dockerfile_hash(Preset::Full, &docker_cli), full);
+    }
+
+    #[test]
+    fn test_parse_hash_labels() {
+        assert_eq!(
+            parse_hash_labels("0123456789abcdef <no value>\n"),
+            ["0123456789abcdef"]
+        );
+        assert!(parse_hash_labels("<no value> <no value>\n").is_empty());
+        // Podman prints nothing for missing labels
+        assert!(parse_hash_labels(" \n").is_empty());
+    }
+
+    #[test]
+    fn test_registry() {
+        assert_eq!(registry("ghcr.io/acme/jail-dev:latest"), Some("ghcr.io"));
+        assert_eq!(registry("localhost:5000/jail-dev"), Some("localhost:5000"));
+        assert_eq!(registry("localhost/jail-dev"), Some("localhost"));
+        assert_eq!(registry("acme/jail-dev:latest"), None);
+        assert_eq!(registry("jail-dev:latest"), None);
+        assert_eq!(
+            login_hint(Runtime::Podman, "quay.io/acme/dev"),
+            "podman login quay.io"
+        );
+        assert_eq!(login_hint(Runtime::Docker, "acme/dev"), "docker login");
+    }
+
+    #[test]
+    fn test_package() {
+        for name in [
+            "jq",
+            "postgresql-client",
+            "g++",
+            "libc6:amd64",
+            "ripgrep=14.1.0-1~ubuntu",
+        ] {
+            assert_eq!(name.parse::<Package>().unwrap().to_string(), name);
+        }
+        for name in ["", "-y", "jq; rm -rf /", "$(id)", "Jq", "a b", "jq\nrm"] {
+            assert!(name.parse::<Package>().is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_build_args_use_empty_context() {
+        // Run from a non-empty directory, which must not become the context
+        let cwd = std::env::current_dir().unwrap();
+        assert!(std::fs::read_dir(&cwd).unwrap().next().is_some());
+
+        let context = std::env::temp_dir().join("jail-build-test");
+        let args = build_args(
+            "jail-dev:latest",
+            Preset::Full,
+            None,
+            true,
+            Some((1001, 1002)),
+            &Extras {
+                packages: vec!["jq".parse().unwrap(), "ripgrep".parse().unwrap()],
+                docker_cli: true,
+            },
+            &context,
+        );
+        assert!(!args.iter().any(|arg| arg == "."));
+        assert!(!args.iter().any(|arg| *arg == cwd.display().to_string()));
+        assert_eq!(args.last().unwrap(), &context.display().to_string());
+        assert_eq!(&args[args.len() - 3..args.len() - 1], ["-f", "-"]);
+        assert!(args.contains(&"--no-cache".to_string()));
+        assert!(args.contains(&"USER_UID=1001".to_string()));
+        assert!(args.contains(&"EXTRA_PACKAGES=jq ripgrep".to_string()));
+        assert!(args.contains(&"DOCKER_CLI=1".to_string()));
+        assert!(args.contains(&jail::CREATED_BY_LABEL.to_string()));
+        assert!(args.contains(&"io.jail.preset=full".to_string()));
+        // The base hash leaves out the extras
+        let base = format!(
+            "io.jail.base-dockerfile-hash={}",
+            dockerfile_hash(Preset::Full, &Extras::default())
+        );
+        assert!(args.contains(&base));
+
+        // Pushed images keep the Dockerfile's default IDs
+        let portable = build_args(
+            "ghcr.io/acme/jail-dev:latest",
+            Preset::Full,
+            None,
+            false,
+            None,
+            &Extras::default(),
+            &context,
+        );
+        assert!(!portable.iter().any(|arg| arg.starts_with("USER_")));
+    }
+
+    #[test]
+    fn test_parse_base_labels() {
+        let hash = dockerfile_hash(Preset::Rust, &Extras::default());
+        assert_eq!(
+            parse_base_labels(&format!("rust {}\n", hash)),
+            Some((Preset::Rust, hash))
+        );
+        // Docker shows missing labels as `<no value>`, Podman as nothing
+        assert_eq!(parse_base_labels("<no value> <no value>\n"), None);
+        assert_eq!(parse_base_labels(" \n"), None);
+        assert_eq!(parse_base_labels("rust\n"), None);
     }
 }
@@ -0,0 +1,180 @@
+//! Sandboxed dev environments via containers: the library behind the `jail` CLI.
+//!
+//! Jails can be managed with [`Jail`] and the functions at the top level. These
+//! return data and errors: nothing is printed and nothing is asked. Where the
+//! CLI prompts, they take the answer as an argument or fail.
+//!
+//! Only Unix hosts (Linux and macOS) are supported.
+//!
+//! ```no_run
+//! for info in jail_cli::list_jails()? {
+//!     println!("{} ({})", info.name, if info.running { "running" } else { "stopped" });
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+#[cfg(not(unix))]
+compile_error!("jail-cli only supports Unix hosts (Linux and macOS)");
+
+// The commands of the `jail` binary, which writes to the terminal and prompts
+// through them. They are not part of the library's API.
+#[doc(hidden)]
+pub mod cache;
+#[doc(hidden)]
+pub mod completions;
+#[doc(hidden)]
+pub mod config;
+mod container;
+mod devcontainer;
+#[doc(hidden)]
+pub mod doctor;
+mod editor;
+mod gui;
+mod hooks;
+#[doc(hidden)]
+pub mod image;
+#[doc(hidden)]
+pub mod jail;
+mod lock;
+#[doc(hidden)]
+pub mod network;
+#[doc(hidden)]
+pub mod output;
+#[doc(hidden)]
+pub mod port;
+#[doc(hidden)]
+pub mod process;
+#[doc(hidden)]
+pub mod runtime;
+mod schema;
+#[doc(hidden)]
+pub mod shell;
+#[doc(hidden)]
+pub mod trash;
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+pub use image::{Platform, Preset};
+pub use jail::{
+    CloneOptions, ContainerOptions, GitProtocol, GitState, JailInfo, JailMetadata, Mount,
+};
+pub use network::NetworkMode;
+pub use port::{PortMapping, Protocol};
+pub use runtime::Runtime;
+pub use shell::Shell;
+
+/// A jail on this machine
+#[derive(Debug)]
+pub struct Jail {
+    pub name: String,
+    /// Directory holding jail.toml and, usually, the workspace
+    pub dir: PathBuf,
+    pub metadata: JailMetadata,
+}
+
+impl Jail {
+    /// Open a jail by its exact name
+    pub fn open(name: &str) -> Result<Self> {
+        let dir = jail::jail_path(name)?;
+        if !dir.exists() {
+            bail!("Jail '{}' not found", name);
+        }
+        let metadata = JailMetadata::load(&dir)?;
+        Ok(Self {
+            name: name.to_string(),
+            dir,
+            metadata,
+        })
+    }
+
+    /// Find a jail by its name or part of it. When several jails match,
+    /// `choose` returns the index of the one to use.
+    ///
+    /// ```no_run
+    /// // Take the first match rather than asking
+    /// let jail = jail_cli::Jail::find("api", |_candidates| Ok(0))?;
+    /// println!("{}", jail.name);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn find(filter: &str, choose: impl FnOnce(&[String]) -> Result<usize>) -> Result<Self> {
+        let (name, _) = jail::select_jail_with(Some(filter), None, choose)?;
+        Self::open(&name)
+    }
+
+    /// Host directory of the workspace (none when the project lives inside an
+    /// adopted container)
+    pub fn workspace(&self) -> Option<PathBuf> {
+        self.metadata.workspace_path(&self.dir)
+    }
+}
+
+/// All jails, leaving out any whose jail.toml can't be read
+pub fn list_jails() -> Result<Vec<JailInfo>> {
+    jail::load_jail_infos().map(|(infos, _)| infos)
+}
+
+/// Start a jail's container, creating it first if needed, and return its ID
+///
+/// ```no_run
+/// let jail = jail_cli::Jail::open("acme/api")?;
+/// let id = jail_cli::container_for(&jail)?;
+/// std::process::Command::new(jail.metadata.runtime.command())
+///     .args(["exec", &id, "cargo", "test"])
+///     .status()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn container_for(jail: &Jail) -> Result<String> {
+    jail::start_container(&jail.name)
+}
+
+/// Create an empty jail with the given preset (default: default_preset in config),
+/// on `runtime` if given, otherwise on the configured or detected one
+pub fn create(
+    name: &str,
+    options: ContainerOptions,
+    preset: Option<Preset>,
+    runtime: Option<Runtime>,
+) -> Result<Jail> {
+    jail::create_jail(name, options, preset, None, false, runtime)?;
+    Jail::open(name)
+}
+
+/// Clone repositories or local directories into a new jail, named after the
+/// first source unless `name` is given, on `runtime` if given, otherwise on
+/// the configured or detected one
+///
+/// ```no_run
+/// let jail = jail_cli::clone(
+///     &["https://github.com/acme/api".to_string()],
+///     None,
+///     Default::default(),
+///     Default::default(),
+///     None,
+/// )?;
+/// println!("cloned into {}", jail.name);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn clone(
+    sources: &[String],
+    name: Option<&str>,
+    options: ContainerOptions,
+    clone_options: CloneOptions,
+    runtime: Option<Runtime>,
+) -> Result<Jail> {
+    let name = jail::clone_jail(sources, name, options, clone_options, runtime)?;
+    Jail::open(&name)
+}
+
+/// Remove a jail, moving it to the trash. Fails if its workspace has
+/// uncommitted or unpushed work, unless `force` is set.
+pub fn remove(jail: Jail, force: bool) -> Result<()> {
+    jail::remove_jail(
+        &jail.name,
+        jail::RemoveOptions {
+            yes: true,
+            force,
+            keep_workspace: false,
+        },
+    )
+}
@@ -0,0 +1,246 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::output::{step, warning};
+
+/// Lock file in each jail directory, holding the pid of the process that locked it
+const LOCK_FILE: &str = "jail.lock";
+
+/// Locked while breaking a stale lock, so two processes breaking it at once
+/// can't remove each other's new lock file
+const BREAK_LOCK_FILE: &str = "jail.lock.break";
+
+/// How long to wait for another jail command to finish before giving up
+const WAIT: Duration = Duration::from_secs(10);
+
+/// Exclusive advisory lock on a jail, released when dropped
+#[derive(Debug)]
+pub(crate) struct JailLock {
+    _file: File,
+}
+
+/// Lock an existing jail for a mutating operation, waiting briefly if another
+/// process holds it
+pub(crate) fn acquire(jail_dir: &Path, name: &str) -> Result<JailLock> {
+    acquire_within(jail_dir, name, WAIT)
+}
+
+/// Create a new jail's directory and lock it. Creating the directory is the
+/// claim on the name, so two concurrent creates can't both succeed.
+pub(crate) fn create(jail_dir: &Path, name: &str) -> Result<JailLock> {
+    if let Some(parent) = jail_dir.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    match std::fs::create_dir(jail_dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            bail!("Jail '{}' already exists", name)
+        }
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to create directory: {}", jail_dir.display()))
+        }
+    }
+    acquire_within(jail_dir, name, WAIT)
+}
+
+fn acquire_within(jail_dir: &Path, name: &str, wait: Duration) -> Result<JailLock> {
+    let path = jail_dir.join(LOCK_FILE);
+    let start = Instant::now();
+    let mut waiting = false;
+
+    loop {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        match file.try_lock() {
+            // A lock file broken as stale meanwhile no longer locks anything
+            Ok(()) if !is_same_file(&path, &file) => continue,
+            Ok(()) => return hold(&path, file),
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock jail '{}'", name))
+            }
+        }
+
+        let holder = read_pid(&path);
+        if let Some(pid) = holder.filter(|pid| !is_alive(*pid)) {
+            // The lock outlived the process that took it, e.g. through a descriptor
+            // leaked to a leftover child. Start over with a fresh lock file.
+            warning!(
+                "Breaking stale lock on jail '{}' (pid {} is gone)",
+                name,
+                pid
+            );
+            break_stale(&path, &file)?;
+            continue;
+        }
+
+        let by = holder
+            .map(|pid| format!(" (locked by pid {})", pid))
+            .unwrap_or_default();
+        if start.elapsed() >= wait {
+            bail!("Jail '{}' is busy{}", name, by);
+        }
+        if !waiting {
+            step!("Waiting for jail '{}'{}...", name.cyan(), by);
+            waiting = true;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/// Lock a jail only if no one else holds it, for writes that can be skipped
+pub(crate) fn try_acquire(jail_dir: &Path) -> Option<JailLock> {
+    let path = jail_dir.join(LOCK_FILE);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .ok()?;
+    file.try_lock().ok()?;
+    if !is_same_file(&path, &file) {
+        return None;
+    }
+    hold(&path, file).ok()
+}
+
+/// Record the holder of a locked file for other processes' error messages
+fn hold(path: &Path, mut file: File) -> Result<JailLock> {
+    file.set_len(0)
+        .and_then(|()| write!(file, "{}", std::process::id()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(JailLock { _file: file })
+}
+
+/// Remove a stale lock file, unless another process has already replaced it
+fn break_stale(path: &Path, stale: &File) -> Result<()> {
+    let break_path = path.with_file_name(BREAK_LOCK_FILE);
+    let breaking = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&break_path)
+        .with_context(|| format!("Failed to open {}", break_path.display()))?;
+    // Only held for a check and a removal, so it's never stale itself
+    breaking
+        .lock()
+        .with_context(|| format!("Failed to lock {}", break_path.display()))?;
+    if is_same_file(path, stale) {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Whether `path` still names the file that is open as `file`
+fn is_same_file(path: &Path, file: &File) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(path), file.metadata()) {
+        (Ok(a), Ok(b)) => (a.dev(), a.ino()) == (b.dev(), b.ino()),
+        _ => false,
+    }
+}
+
+/// Pid recorded in a lock file
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether a process with this pid exists
+fn is_alive(pid: u32) -> bool {
+    if Path::new("/proc/self").exists() {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+    // No procfs (macOS)
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_jail(test: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("jail-test-lock-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_lock_contention() {
+        let dir = temp_jail("contention");
+        let held = create(&dir, "repo").unwrap();
+
+        let err = acquire_within(&dir, "repo", Duration::from_millis(300)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Jail 'repo' is busy (locked by pid {})", std::process::id())
+        );
+        assert!(create(&dir, "repo")
+            .unwrap_err()
+            .to_string()
+            .contains("already exists"));
+
+        drop(held);
+        assert!(acquire_within(&dir, "repo", Duration::ZERO).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stale_lock_is_broken() {
+        let dir = temp_jail("stale");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A lock still held, but recorded for a process that no longer exists
+        let path = dir.join(LOCK_FILE);
+        std::fs::write(&path, "999999999").unwrap();
+        let leaked = File::open(&path).unwrap();
+        leaked.lock().unwrap();
+
+        let lock = acquire_within(&dir, "repo", Duration::ZERO);
+        assert!(lock.is_ok());
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+
+        drop(leaked);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_break_stale_keeps_a_new_lock() {
+        let dir = temp_jail("break");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(LOCK_FILE);
+        std::fs::write(&path, "999999999").unwrap();
+        let stale = File::open(&path).unwrap();
+
+        // Another process broke the lock first and holds a new one
+        std::fs::remove_file(&path).unwrap();
+        let held = acquire_within(&dir, "repo", Duration::ZERO).unwrap();
+        break_stale(&path, &stale).unwrap();
+        assert!(path.exists());
+        assert!(acquire_within(&dir, "repo", Duration::ZERO).is_err());
+
+        drop(held);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
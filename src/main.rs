@@ -1,103 +1,1110 @@
-mod config;
-mod image;
-mod jail;
-mod runtime;
-
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use colored::Colorize;
+use jail_cli::{
+    cache, completions, config, doctor, image, jail, network, output, port, process, runtime,
+    shell, trash,
+};
 
 #[derive(Parser)]
 #[command(name = "jail")]
 #[command(about = "Sandboxed dev environments via containers", long_about = None)]
 struct Cli {
+    /// Never prompt; fail instead when a choice is needed (implied when stdin is not a TTY)
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// Match jail names by prefix only, and always ask when several match
+    #[arg(long, global = true)]
+    no_fuzzy: bool,
+
+    /// Don't offer to start a stopped Podman machine
+    #[arg(long, global = true)]
+    no_auto_start: bool,
+
+    /// Container runtime to use instead of detecting one (overrides JAIL_RUNTIME and config);
+    /// existing jails must have been created with it
+    #[arg(long, global = true, value_enum)]
+    runtime: Option<runtime::Runtime>,
+
+    /// Hide progress output and log git/build output to a file, shown only on failure
+    /// (`list` prints just the jail names)
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print every container runtime and git command before running it
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Disable colored output (also NO_COLOR; on by default only when stdout is a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Container settings shared by commands that create or update containers
+#[derive(Args)]
+struct ContainerArgs {
+    /// Ports to expose as PORT, HOST:CONTAINER, PORT/udp or a range like 9000-9010
+    /// (can be specified multiple times)
+    #[arg(short, long = "port", action = clap::ArgAction::Append)]
+    ports: Vec<port::PortRange>,
+    /// Memory limit (e.g. 512m, 4g)
+    #[arg(long, value_parser = jail::parse_memory)]
+    memory: Option<String>,
+    /// Number of CPUs (e.g. 1.5)
+    #[arg(long, value_parser = jail::parse_cpus)]
+    cpus: Option<f64>,
+    /// GPUs to pass through: all, a count, or device=0,1 (Linux with NVIDIA Container Toolkit).
+    /// none stops passing them through
+    #[arg(long, value_parser = jail::parse_gpus)]
+    gpus: Option<String>,
+    /// Extra mount as host:container[:ro] (can be specified multiple times)
+    #[arg(long = "mount", value_parser = jail::parse_mount, action = clap::ArgAction::Append)]
+    mounts: Vec<jail::Mount>,
+    /// Environment variable as KEY=VALUE, or KEY to copy from the host (can be specified multiple times)
+    #[arg(short, long, value_parser = jail::parse_env, action = clap::ArgAction::Append)]
+    env: Vec<(String, String)>,
+    /// Shell to use inside the jail
+    #[arg(long, value_enum)]
+    shell: Option<shell::Shell>,
+    /// Network mode (default: host on Linux, bridge on macOS, or default_network in config).
+    /// Services on the host are reachable at $JAIL_HOST_ADDR: localhost with host
+    /// networking on Linux, host.docker.internal or host.containers.internal otherwise
+    #[arg(long, value_enum, conflicts_with = "no_network")]
+    network: Option<network::NetworkMode>,
+    /// Disable networking (same as --network none)
+    #[arg(long)]
+    no_network: bool,
+    /// User the container runs as (default: dev, or user in config)
+    #[arg(long, value_parser = jail::parse_user)]
+    user: Option<String>,
+    /// Lock the container down for untrusted code: no capabilities, sudo or package
+    /// installs, read-only system (only the workspace and home are writable)
+    #[arg(long)]
+    hardened: bool,
+    /// Mount the host's Docker or Podman socket so docker and docker compose work
+    /// inside the jail. This gives the jail control of the host: asks first.
+    #[arg(long)]
+    docker_socket: bool,
+    /// Stop mounting the host's runtime socket into a jail created with --docker-socket
+    #[arg(long, conflicts_with = "docker_socket")]
+    no_docker_socket: bool,
+    /// Let GUI apps open windows on the host's display (X11 or Wayland on Linux,
+    /// XQuartz on macOS)
+    #[arg(long)]
+    gui: bool,
+    /// Share the host's IPC namespace, which X11 apps in --gui jails use for shared
+    /// memory (MIT-SHM). Code in the jail can then reach that of host processes.
+    #[arg(long)]
+    host_ipc: bool,
+    /// Pass the host's Claude Code login (~/.claude and ~/.claude.json) and
+    /// ANTHROPIC_API_KEY into the jail. Code running in it can use and read them.
+    #[arg(long)]
+    ai_credentials: bool,
+    /// Share the host's git credential store (credential.helper store) read-only, so
+    /// HTTPS pushes work without logging in again
+    #[arg(long)]
+    git_credentials: bool,
+    /// Create the container even if a host port it publishes is already in use
+    #[arg(long)]
+    force: bool,
+}
+
+/// Options of `jail list` and its `ls` alias
+#[derive(Args)]
+struct ListArgs {
+    /// Output as JSON
+    #[arg(long, conflicts_with = "group_by")]
+    json: bool,
+    /// Sort order
+    #[arg(long, value_enum, default_value_t)]
+    sort: jail::ListSort,
+    /// Show the disk usage of each jail
+    #[arg(long)]
+    size: bool,
+    /// Also show broken jails (missing or unparsable jail.toml)
+    #[arg(short, long)]
+    all: bool,
+    /// Don't show the branch and dirty state (*) of git workspaces
+    #[arg(long)]
+    no_git: bool,
+    /// Only show running jails
+    #[arg(long, conflicts_with = "stopped")]
+    running: bool,
+    /// Only show stopped jails
+    #[arg(long)]
+    stopped: bool,
+    /// Only show jails using this container runtime
+    #[arg(long, value_enum, value_name = "RUNTIME")]
+    on_runtime: Option<runtime::Runtime>,
+    /// Only show jails of this owner (the part before the / in owner/repo)
+    #[arg(long)]
+    owner: Option<String>,
+    /// Print jails under headings
+    #[arg(long, value_enum)]
+    group_by: Option<jail::ListGroup>,
+}
+
+impl ListArgs {
+    fn into_options(self) -> jail::ListOptions {
+        jail::ListOptions {
+            json: self.json,
+            sort: self.sort,
+            size: self.size,
+            all: self.all,
+            no_git: self.no_git,
+            filter: jail::ListFilter {
+                running: self.running,
+                stopped: self.stopped,
+                runtime: self.on_runtime,
+                owner: self.owner,
+            },
+            group_by: self.group_by,
+        }
+    }
+}
+
+impl From<ContainerArgs> for jail::ContainerOptions {
+    fn from(args: ContainerArgs) -> Self {
+        Self {
+            ports: port::expand(args.ports),
+            memory: args.memory,
+            cpus: args.cpus,
+            gpus: args.gpus,
+            mounts: args.mounts,
+            env: args.env,
+            shell: args.shell,
+            network: if args.no_network {
+                Some(network::NetworkMode::None)
+            } else {
+                args.network
+            },
+            user: args.user,
+            hardened: args.hardened,
+            docker_socket: if args.no_docker_socket {
+                Some(false)
+            } else {
+                args.docker_socket.then_some(true)
+            },
+            gui: args.gui,
+            host_ipc: args.host_ipc,
+            ai_credentials: args.ai_credentials,
+            git_credentials: args.git_credentials,
+            force: args.force,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Clone a git repository or local path into a sandboxed environment
     Clone {
-        /// Git URL or local path to clone
-        source: String,
-        /// Name for the jail (default: derived from source)
+        /// Git URLs, local paths or owner/repo (on default_git_host, github.com unless set)
+        /// to clone; several sources each get their own directory in the workspace
+        #[arg(required = true)]
+        sources: Vec<String>,
+        /// Name for the jail (default: derived from source; required for several sources)
         #[arg(short, long)]
         name: Option<String>,
-        /// Ports to expose (can be specified multiple times)
-        #[arg(short, long = "port", action = clap::ArgAction::Append)]
-        ports: Vec<u16>,
+        #[command(flatten)]
+        container: ContainerArgs,
+        /// Branch to check out
+        #[arg(short, long, conflicts_with = "commit")]
+        branch: Option<String>,
+        /// Commit to check out
+        #[arg(long)]
+        commit: Option<String>,
+        /// Create a shallow clone with the given number of commits
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        depth: Option<u32>,
+        /// Don't enter the jail after cloning
+        #[arg(long)]
+        no_enter: bool,
+        /// Ignore the repository's devcontainer.json
+        #[arg(long)]
+        no_devcontainer: bool,
+        /// Copy local paths completely, without applying .gitignore/.jailignore
+        #[arg(long)]
+        no_ignore: bool,
+        /// Mount a local path as the workspace instead of copying it. Changes in the jail
+        /// show up in the original directory and the other way round, trading isolation
+        /// for speed; `jail remove` never deletes it.
+        #[arg(long, conflicts_with = "no_ignore")]
+        link: bool,
+        /// Toolchains in the base image (default: default_preset in config, or full)
+        #[arg(long, value_enum)]
+        preset: Option<image::Preset>,
+        /// Platform to run the jail as, e.g. linux/amd64 on Apple Silicon (default: native)
+        #[arg(long, value_enum)]
+        platform: Option<image::Platform>,
+        /// Only check out this subdirectory (sparse checkout), and start there
+        #[arg(long, value_parser = jail::parse_sparse_path)]
+        path: Option<String>,
+        /// Leave the jail in place if creating it fails, for debugging
+        #[arg(long)]
+        keep_partial: bool,
+        /// Clone owner/repo sources over HTTPS
+        #[arg(long, conflicts_with = "ssh")]
+        https: bool,
+        /// Clone owner/repo sources over SSH (default when an SSH agent or key is found)
+        #[arg(long)]
+        ssh: bool,
+        /// Directory to start shells and commands in, relative to the workspace
+        /// (change later with `jail config set --jail NAME workdir PATH`)
+        #[arg(long, value_parser = jail::parse_workdir)]
+        workdir: Option<String>,
+    },
+    /// Clone another repository into an existing jail's workspace
+    Add {
+        /// Name or filter for the jail
+        name: String,
+        /// Git URL, local path or owner/repo to clone
+        source: String,
+        /// Copy local paths completely, without applying .gitignore/.jailignore
+        #[arg(long)]
+        no_ignore: bool,
     },
     /// Create an empty jail
     Create {
         /// Name for the jail
         name: String,
-        /// Ports to expose (can be specified multiple times)
-        #[arg(short, long = "port", action = clap::ArgAction::Append)]
-        ports: Vec<u16>,
+        #[command(flatten)]
+        container: ContainerArgs,
+        /// Don't enter the jail after creating it
+        #[arg(long)]
+        no_enter: bool,
+        /// Leave the jail in place if creating it fails, for debugging
+        #[arg(long)]
+        keep_partial: bool,
+        /// Toolchains in the base image (default: default_preset in config, or full)
+        #[arg(long, value_enum)]
+        preset: Option<image::Preset>,
+        /// Platform to run the jail as, e.g. linux/amd64 on Apple Silicon (default: native)
+        #[arg(long, value_enum)]
+        platform: Option<image::Platform>,
+    },
+    /// Manage an existing container or host directory as a jail, without copying it
+    Adopt {
+        /// Name for the jail
+        name: String,
+        /// Container to take over, by name or ID (it is renamed to the jail's container name)
+        #[arg(long, required_unless_present = "dir", conflicts_with_all = ["dir", "preset"])]
+        container: Option<String>,
+        /// Directory to use as the workspace in place; `jail remove` never deletes it
+        #[arg(long)]
+        dir: Option<std::path::PathBuf>,
+        /// Toolchains in the base image (default: default_preset in config, or full)
+        #[arg(long, value_enum)]
+        preset: Option<image::Preset>,
     },
     /// List all jails
-    List,
+    List(ListArgs),
     /// Alias for list
     #[command(hide = true)]
-    Ls,
+    Ls(ListArgs),
+    /// Find jails with missing or broken metadata and offer fixes
+    Repair,
+    /// Remove containers and home volumes of jails that no longer exist
+    Prune {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show disk usage of all jails, largest first
+    Du,
+    /// Show details for a jail
+    Info {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Enter a jail's shell
+    #[command(allow_negative_numbers = true)]
     Enter {
-        /// Name or filter for the jail (interactive selection if multiple match)
+        /// Name or filter for the jail (interactive selection if multiple match),
+        /// or - for the most recently used jail (-2 for the one before, ...)
         name: Option<String>,
-        /// Ports to expose (can be specified multiple times, will recreate container if needed)
-        #[arg(short, long = "port", action = clap::ArgAction::Append)]
-        ports: Vec<u16>,
+        #[command(flatten)]
+        container: ContainerArgs,
+        /// Directory to start in, relative to the workspace (default: the jail's workdir)
+        #[arg(long, value_parser = jail::parse_workdir)]
+        workdir: Option<String>,
+        /// Leave the container running after the shell exits
+        #[arg(long)]
+        keep_running: bool,
+        /// Open the shell as root, without changing the container's user
+        #[arg(long, conflicts_with = "tmux")]
+        root: bool,
+        /// Run the shell in a tmux session that survives disconnects (see `jail attach`)
+        #[arg(long)]
+        tmux: bool,
+        /// Run the jail's post_create hooks again
+        #[arg(long)]
+        rerun_hooks: bool,
+        /// Recreate the container without asking when it doesn't match jail.toml
+        #[arg(long)]
+        auto_recreate: bool,
     },
     /// Alias for enter
-    #[command(hide = true)]
+    #[command(hide = true, allow_negative_numbers = true)]
     Start {
         name: Option<String>,
-        #[arg(short, long = "port", action = clap::ArgAction::Append)]
-        ports: Vec<u16>,
+        #[command(flatten)]
+        container: ContainerArgs,
+        #[arg(long, value_parser = jail::parse_workdir)]
+        workdir: Option<String>,
+        #[arg(long)]
+        keep_running: bool,
+        #[arg(long, conflicts_with = "tmux")]
+        root: bool,
+        #[arg(long)]
+        tmux: bool,
+        #[arg(long)]
+        rerun_hooks: bool,
+        #[arg(long)]
+        auto_recreate: bool,
     },
-    /// Remove a jail
-    Remove {
+    /// Re-attach to a jail's tmux session
+    Attach {
         /// Name or filter for the jail (interactive selection if multiple match)
         name: Option<String>,
+        /// Leave the container running after the session ends
+        #[arg(long)]
+        keep_running: bool,
+    },
+    /// Update a jail's workspace from its sources: fast-forward git repositories,
+    /// copy files changed in local directories
+    Pull {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Rebase local commits onto the upstream instead of refusing to pull
+        #[arg(long)]
+        rebase: bool,
+        /// Stash uncommitted changes around the pull instead of refusing to pull
+        #[arg(long)]
+        autostash: bool,
+        /// Run git inside the container with the forwarded SSH agent
+        /// (or set pull_in_container = true in config.toml)
+        #[arg(long)]
+        in_container: bool,
+    },
+    /// Run a command inside a jail without an interactive shell
+    Exec {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Command to run, given after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Run a command in a throwaway container with a jail's workspace and settings,
+    /// leaving the jail's container alone
+    Run {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Image to run instead of the jail's, e.g. to test against another base
+        #[arg(long)]
+        image: Option<String>,
+        /// Command to run, given after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Copy files between the host and a jail (use name:path for jail paths)
+    Cp {
+        /// Source path (host path or name:path)
+        src: String,
+        /// Destination path (host path or name:path)
+        dst: String,
+        /// Overwrite existing files
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Show live CPU, memory and network usage of running jails
+    Stats {
+        /// Include stopped jails
+        #[arg(short, long)]
+        all: bool,
+        /// Refresh every couple of seconds until interrupted
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Stop a jail's container without removing it
+    Stop {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Stop all running jails
+        #[arg(short, long, conflicts_with = "name")]
+        all: bool,
+    },
+    /// Remove a jail, moving it to the trash (see `jail trash`)
+    Remove {
+        /// Jail names; a single one may be a filter (interactive selection if it matches several)
+        names: Vec<String>,
+        /// Remove every jail
+        #[arg(short, long, conflicts_with_all = ["names", "filter"])]
+        all: bool,
+        /// Remove every jail matching a filter (owner or repo name prefix)
+        #[arg(long, conflicts_with = "names")]
+        filter: Option<String>,
+        /// Don't ask for confirmation when names aren't given exactly
+        #[arg(short, long)]
+        yes: bool,
+        /// Delete workspaces with uncommitted changes or unpushed commits without asking
+        #[arg(short, long)]
+        force: bool,
+        /// Keep the workspace in the trash directory (trash_dir in config) for good,
+        /// deleting the rest of the jail instead of moving it to the trash
+        #[arg(long)]
+        keep_workspace: bool,
     },
     /// Alias for remove
     #[command(hide = true)]
-    Rm { name: Option<String> },
-    /// Open VSCode attached to a jail's container
+    Rm {
+        names: Vec<String>,
+        #[arg(short, long, conflicts_with_all = ["names", "filter"])]
+        all: bool,
+        #[arg(long, conflicts_with = "names")]
+        filter: Option<String>,
+        #[arg(short, long)]
+        yes: bool,
+        #[arg(short, long)]
+        force: bool,
+        #[arg(long)]
+        keep_workspace: bool,
+    },
+    /// List, restore or delete removed jails (kept for trash_retention_days in config, default 7)
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+    /// Copy a jail, including its installed container state, under a new name
+    Duplicate {
+        /// Name or filter for the jail to copy
+        name: String,
+        /// Name for the new jail
+        new_name: String,
+        /// Ports for the new jail, replacing the copied ones (can be specified multiple times)
+        #[arg(short, long = "port", action = clap::ArgAction::Append)]
+        ports: Vec<port::PortRange>,
+        /// Create the container even if a host port it publishes is already in use
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rename a jail, along with its container, home volume and snapshots
+    Rename {
+        /// Name or filter for the jail to rename
+        name: String,
+        /// New name for the jail
+        #[arg(value_parser = jail::parse_jail_name)]
+        new_name: String,
+        /// Stop the jail's container if it's running
+        #[arg(long)]
+        force: bool,
+    },
+    /// Export a jail to a tar.gz archive
+    Export {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Archive path (default: <name>.tar.gz in the current directory)
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+        /// Also save the container's installed state and home directory
+        /// (without it, the home volume is not exported)
+        #[arg(long)]
+        include_container: bool,
+    },
+    /// Import a jail from an archive created by export
+    Import {
+        /// Archive to import
+        archive: std::path::PathBuf,
+        /// Name for the imported jail (default: the exported name)
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// List, add or remove a jail's published ports
+    #[command(args_conflicts_with_subcommands = true)]
+    Ports {
+        #[command(subcommand)]
+        command: Option<PortsCommands>,
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+    },
+    /// Print the URLs a running jail's ports are reachable at from the host
+    Url {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Open the URL of this port (host or container side) in the browser
+        #[arg(long, value_name = "PORT")]
+        open: Option<u16>,
+    },
+    /// Print a jail's runtime, container name and ID, image and shell command, for
+    /// running docker or podman against it directly
+    #[command(alias = "inspect-container")]
+    Which {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Output as JSON
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+        /// Print only a template of fields: {{runtime}}, {{container}}, {{id}},
+        /// {{running}}, {{image}} and {{exec}}, e.g. '{{id}}'
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Save the current state of a jail's container and home directory as a snapshot
+    Snapshot {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Label for the snapshot (default: current time)
+        #[arg(short, long, value_parser = jail::parse_snapshot_tag)]
+        tag: Option<String>,
+    },
+    /// Recreate a jail's container and home directory from a snapshot
+    Restore {
+        /// Name or filter for the jail
+        name: String,
+        /// Label of the snapshot to restore
+        tag: String,
+    },
+    /// Move a jail to another container runtime, keeping its container state
+    Migrate {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        #[arg(conflicts_with = "all")]
+        name: Option<String>,
+        /// Migrate every jail not on the target runtime yet
+        #[arg(long)]
+        all: bool,
+        /// Runtime to move to
+        #[arg(long, value_enum)]
+        to: runtime::Runtime,
+    },
+    /// Open VSCode (or a compatible editor) attached to a jail's container
     Code {
         /// Name or filter for the jail (interactive selection if multiple match)
         name: Option<String>,
+        /// Editor to open: code, code-insiders, cursor, windsurf or another binary (default: editor in config)
+        #[arg(long)]
+        editor: Option<String>,
+        /// Ports to expose as PORT, HOST:CONTAINER, PORT/udp or a range like 9000-9010,
+        /// recreating the container (can be specified multiple times)
+        #[arg(short, long = "port", action = clap::ArgAction::Append)]
+        ports: Vec<port::PortRange>,
+        /// Create the container even if a host port it publishes is already in use
+        #[arg(long)]
+        force: bool,
+        /// Recreate the container without asking when it doesn't match jail.toml
+        #[arg(long)]
+        auto_recreate: bool,
     },
+    /// Print the host path of a jail's workspace (e.g. `cd $(jail path myrepo)`)
+    Path {
+        /// Name or filter for the jail (interactive selection if multiple match)
+        name: Option<String>,
+        /// Print the jail directory (containing jail.toml) instead
+        #[arg(long)]
+        jail_dir: bool,
+    },
+    /// Check runtimes, images, host tools and jails for problems, with suggested fixes
+    /// (exits non-zero when something is broken)
+    Doctor,
     /// Check runtime health status
     Status,
+    /// Generate shell completions
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Read or change settings in config.toml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Manage the base image
+    Image {
+        #[command(subcommand)]
+        command: ImageCommands,
+    },
+    /// Manage the package caches shared between jails (shared_caches = true in config.toml).
+    /// Shared caches trade isolation for speed: every jail can read and modify packages
+    /// downloaded by the others, so a malicious project could poison them.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum PortsCommands {
+    /// Publish more ports (recreates the container)
+    Add {
+        /// Name or filter for the jail
+        name: String,
+        /// Ports as PORT, HOST:CONTAINER, PORT/udp or a range like 9000-9010
+        #[arg(required = true)]
+        ports: Vec<port::PortRange>,
+        /// Recreate the container even if a host port it publishes is already in use
+        #[arg(long)]
+        force: bool,
+    },
+    /// Stop publishing ports, given by their host port (recreates the container)
+    Rm {
+        /// Name or filter for the jail
+        name: String,
+        /// Ports as PORT, HOST:CONTAINER, PORT/udp or a range like 9000-9010
+        #[arg(required = true)]
+        ports: Vec<port::PortRange>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// List removed jails
+    List,
+    /// Bring a removed jail back (its container is recreated on the next enter)
+    Restore {
+        /// Name of the removed jail, or the ID shown by `jail trash list`
+        name: String,
+        /// Name for the restored jail (default: its old name, prompting if taken)
+        #[arg(short = 'n', long = "name")]
+        new_name: Option<String>,
+    },
+    /// Permanently delete all removed jails
+    Empty {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print a setting, or the whole config file
+    Get {
+        /// Setting to print (e.g. runtime, dotfiles.repository)
+        key: Option<String>,
+        /// Read a setting of this jail instead (workdir)
+        #[arg(long, requires = "key")]
+        jail: Option<String>,
+    },
+    /// Change a setting
+    Set {
+        /// Setting to change
+        key: String,
+        /// New value
+        value: String,
+        /// Change a setting of this jail instead (workdir)
+        #[arg(long)]
+        jail: Option<String>,
+    },
+    /// Remove a setting, restoring its default
+    Unset {
+        /// Setting to remove
+        key: String,
+        /// Remove a setting of this jail instead (workdir)
+        #[arg(long)]
+        jail: Option<String>,
+    },
+    /// Print the path of the config file
+    Path,
+}
+
+#[derive(Subcommand)]
+enum ImageCommands {
+    /// Rebuild the base image
+    Rebuild {
+        /// Do not use the build cache
+        #[arg(long)]
+        no_cache: bool,
+        /// Preset to rebuild
+        #[arg(long, value_enum, default_value_t)]
+        preset: image::Preset,
+        /// Platform variant to rebuild (default: native)
+        #[arg(long, value_enum)]
+        platform: Option<image::Platform>,
+    },
+    /// Print the Dockerfile used for the base image
+    Show {
+        /// Preset to print
+        #[arg(long, value_enum, default_value_t)]
+        preset: image::Preset,
+    },
+    /// Build the base image without your user's IDs and push it to a registry, for a
+    /// team to share as base_image
+    Push {
+        /// Registry reference to push to, e.g. ghcr.io/acme/jail-dev:latest
+        reference: String,
+        /// Preset to push
+        #[arg(long, value_enum, default_value_t)]
+        preset: image::Preset,
+        /// Platform variant to push (default: native)
+        #[arg(long, value_enum)]
+        platform: Option<image::Platform>,
+    },
+    /// Pull the base image from its registry (default: base_image in config)
+    Pull {
+        /// Registry reference to pull
+        reference: Option<String>,
+        /// Build the preset's image locally under that name if pulling fails
+        /// (also build_if_missing in config)
+        #[arg(long)]
+        build_if_missing: bool,
+        /// Preset built when pulling fails
+        #[arg(long, value_enum, default_value_t)]
+        preset: image::Preset,
+        /// Platform variant to pull (default: native)
+        #[arg(long, value_enum)]
+        platform: Option<image::Platform>,
+    },
+    /// Remove the base image
+    Rm {
+        /// Preset to remove
+        #[arg(long, value_enum, default_value_t)]
+        preset: image::Preset,
+        /// Platform variant to remove (default: native)
+        #[arg(long, value_enum)]
+        platform: Option<image::Platform>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Remove the shared cargo, npm and pip caches (jails using them must be stopped)
+    Clear,
 }
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("{} {}", "error:".red().bold(), e);
-        std::process::exit(1);
+    match run() {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{} {}", "error:".red().bold(), e);
+            // A hung runtime usually surfaces as a generic failure, so name the command
+            let timed_out = e.chain().skip(1).find(|cause| {
+                matches!(
+                    cause.downcast_ref(),
+                    Some(process::RunError::TimedOut { .. })
+                )
+            });
+            if let Some(cause) = timed_out {
+                eprintln!("  {}", cause);
+            }
+            std::process::exit(1);
+        }
     }
 }
 
-fn run() -> Result<()> {
+/// Run the command, returning the exit code of a shell or command run in a jail (0 otherwise)
+fn run() -> Result<i32> {
     let cli = Cli::parse();
+    output::set_color(cli.no_color);
+    jail::set_non_interactive(cli.non_interactive);
+    jail::set_no_fuzzy(cli.no_fuzzy);
+    runtime::set_no_auto_start(cli.no_auto_start);
+    output::set_ui(if cli.quiet {
+        output::Ui::Quiet
+    } else {
+        output::Ui::Normal
+    });
+    output::set_verbose(cli.verbose);
+    trash::purge_expired();
 
     match cli.command {
         Commands::Clone {
+            sources,
+            name,
+            container,
+            branch,
+            commit,
+            depth,
+            no_enter,
+            no_devcontainer,
+            no_ignore,
+            link,
+            preset,
+            platform,
+            path,
+            workdir,
+            keep_partial,
+            https,
+            ssh,
+        } => {
+            return jail::clone(
+                &sources,
+                name.as_deref(),
+                container.into(),
+                jail::CloneOptions {
+                    branch,
+                    commit,
+                    depth,
+                    no_devcontainer,
+                    no_ignore,
+                    link,
+                    preset,
+                    platform,
+                    path,
+                    workdir,
+                    protocol: match (https, ssh) {
+                        (true, _) => Some(jail::GitProtocol::Https),
+                        (_, true) => Some(jail::GitProtocol::Ssh),
+                        _ => None,
+                    },
+                    keep_partial,
+                },
+                no_enter,
+                cli.runtime,
+            );
+        }
+        Commands::Add {
+            name,
             source,
+            no_ignore,
+        } => jail::add(&name, &source, no_ignore, cli.runtime)?,
+        Commands::Create {
             name,
+            container,
+            no_enter,
+            keep_partial,
+            preset,
+            platform,
+        } => {
+            return jail::create(
+                &name,
+                container.into(),
+                preset,
+                platform,
+                no_enter,
+                keep_partial,
+                cli.runtime,
+            );
+        }
+        Commands::Adopt {
+            name,
+            container,
+            dir,
+            preset,
+        } => {
+            // clap requires one of --container and --dir
+            let target = match container {
+                Some(container) => jail::Adopt::Container(container),
+                None => jail::Adopt::Dir(dir.unwrap_or_default()),
+            };
+            jail::adopt(&name, target, preset, cli.runtime)?
+        }
+        Commands::List(args) | Commands::Ls(args) => jail::list(cli.quiet, args.into_options())?,
+        Commands::Repair => jail::repair()?,
+        Commands::Prune { yes } => jail::prune(yes)?,
+        Commands::Du => jail::du()?,
+        Commands::Info { name, json } => jail::info(name.as_deref(), json, cli.runtime)?,
+        Commands::Enter {
+            name,
+            container,
+            workdir,
+            keep_running,
+            root,
+            tmux,
+            rerun_hooks,
+            auto_recreate,
+        }
+        | Commands::Start {
+            name,
+            container,
+            workdir,
+            keep_running,
+            root,
+            tmux,
+            rerun_hooks,
+            auto_recreate,
+        } => {
+            return jail::enter(
+                name.as_deref(),
+                container.into(),
+                jail::EnterOptions {
+                    workdir,
+                    keep_running,
+                    root,
+                    tmux,
+                    rerun_hooks,
+                    auto_recreate,
+                },
+                cli.runtime,
+            );
+        }
+        Commands::Attach { name, keep_running } => {
+            return jail::attach(name.as_deref(), keep_running, cli.runtime)
+        }
+        Commands::Pull {
+            name,
+            rebase,
+            autostash,
+            in_container,
+        } => jail::pull(
+            name.as_deref(),
+            jail::PullOptions {
+                rebase,
+                autostash,
+                in_container,
+            },
+            cli.runtime,
+        )?,
+        Commands::Exec { name, command } => {
+            return jail::exec(name.as_deref(), &command, cli.runtime)
+        }
+        Commands::Run {
+            name,
+            image,
+            command,
+        } => return jail::run(name.as_deref(), &command, image.as_deref(), cli.runtime),
+        Commands::Cp { src, dst, force } => jail::cp(&src, &dst, force, cli.runtime)?,
+        Commands::Stats { all, watch } => jail::stats(all, watch)?,
+        Commands::Stop { name, all } => jail::stop(name.as_deref(), all, cli.runtime)?,
+        Commands::Remove {
+            names,
+            all,
+            filter,
+            yes,
+            force,
+            keep_workspace,
+        }
+        | Commands::Rm {
+            names,
+            all,
+            filter,
+            yes,
+            force,
+            keep_workspace,
+        } => jail::remove(
+            &names,
+            filter.as_deref(),
+            all,
+            jail::RemoveOptions {
+                yes,
+                force,
+                keep_workspace,
+            },
+            cli.runtime,
+        )?,
+        Commands::Trash { command } => match command {
+            TrashCommands::List => trash::list()?,
+            TrashCommands::Restore { name, new_name } => {
+                jail::restore_trashed(&name, new_name.as_deref())?
+            }
+            TrashCommands::Empty { yes } => trash::empty(yes)?,
+        },
+        Commands::Duplicate {
+            name,
+            new_name,
             ports,
-        } => jail::clone(&source, name.as_deref(), ports)?,
-        Commands::Create { name, ports } => jail::create(&name, ports)?,
-        Commands::List | Commands::Ls => jail::list()?,
-        Commands::Enter { name, ports } | Commands::Start { name, ports } => {
-            jail::enter(name.as_deref(), ports)?
+            force,
+        } => jail::duplicate(&name, &new_name, port::expand(ports), force, cli.runtime)?,
+        Commands::Rename {
+            name,
+            new_name,
+            force,
+        } => jail::rename(&name, &new_name, force, cli.runtime)?,
+        Commands::Export {
+            name,
+            output,
+            include_container,
+        } => jail::export(
+            name.as_deref(),
+            output.as_deref(),
+            include_container,
+            cli.runtime,
+        )?,
+        Commands::Import { archive, name } => jail::import(&archive, name.as_deref(), cli.runtime)?,
+        Commands::Ports { command, name } => match command {
+            Some(PortsCommands::Add { name, ports, force }) => {
+                jail::add_ports(&name, port::expand(ports), force, cli.runtime)?
+            }
+            Some(PortsCommands::Rm { name, ports }) => {
+                jail::remove_ports(&name, &port::expand(ports), cli.runtime)?
+            }
+            None => jail::ports(name.as_deref(), cli.runtime)?,
+        },
+        Commands::Url { name, open } => jail::url(name.as_deref(), open, cli.runtime)?,
+        Commands::Which { name, json, format } => {
+            jail::which(name.as_deref(), json, format.as_deref(), cli.runtime)?
         }
-        Commands::Remove { name } | Commands::Rm { name } => jail::remove(name.as_deref())?,
-        Commands::Code { name } => jail::code(name.as_deref())?,
-        Commands::Status => jail::status()?,
+        Commands::Snapshot { name, tag } => {
+            jail::snapshot(name.as_deref(), tag.as_deref(), cli.runtime)?
+        }
+        Commands::Restore { name, tag } => jail::restore(&name, &tag, cli.runtime)?,
+        Commands::Migrate { name, all, to } => {
+            jail::migrate(name.as_deref(), all, to, cli.runtime)?
+        }
+        Commands::Code {
+            name,
+            editor,
+            ports,
+            force,
+            auto_recreate,
+        } => jail::code(
+            name.as_deref(),
+            editor.as_deref(),
+            port::expand(ports),
+            auto_recreate,
+            force,
+            cli.runtime,
+        )?,
+        Commands::Path { name, jail_dir } => jail::path(name.as_deref(), jail_dir, cli.runtime)?,
+        Commands::Doctor => return doctor::run(cli.runtime),
+        Commands::Status => jail::status(cli.runtime)?,
+        Commands::Completions { shell } => completions::print(shell, &mut Cli::command()),
+        Commands::Config { command } => match command {
+            ConfigCommands::Get { key, jail } => match (jail, key) {
+                (Some(jail), Some(key)) => jail::get_setting(&jail, &key, cli.runtime)?,
+                (_, key) => config::get(key.as_deref())?,
+            },
+            ConfigCommands::Set { key, value, jail } => match jail {
+                Some(jail) => jail::set_setting(&jail, &key, Some(&value), cli.runtime)?,
+                None => config::set(&key, &value)?,
+            },
+            ConfigCommands::Unset { key, jail } => match jail {
+                Some(jail) => jail::set_setting(&jail, &key, None, cli.runtime)?,
+                None => config::unset(&key)?,
+            },
+            ConfigCommands::Path => println!("{}", config::config_path()?.display()),
+        },
+        Commands::Image { command } => match command {
+            ImageCommands::Rebuild {
+                no_cache,
+                preset,
+                platform,
+            } => image::build(runtime::detect(cli.runtime)?, preset, platform, no_cache)?,
+            ImageCommands::Show { preset } => image::show(preset),
+            ImageCommands::Push {
+                reference,
+                preset,
+                platform,
+            } => image::push(runtime::detect(cli.runtime)?, &reference, preset, platform)?,
+            ImageCommands::Pull {
+                reference,
+                build_if_missing,
+                preset,
+                platform,
+            } => {
+                let Some(reference) = reference.or(config::get_base_image_override()?) else {
+                    anyhow::bail!("No image given and base_image is not set in config");
+                };
+                image::pull_or_build(
+                    runtime::detect(cli.runtime)?,
+                    &reference,
+                    preset,
+                    platform,
+                    build_if_missing || config::build_if_missing()?,
+                )?
+            }
+            ImageCommands::Rm { preset, platform } => {
+                image::remove(runtime::detect(cli.runtime)?, preset, platform)?
+            }
+        },
+        Commands::Cache { command } => match command {
+            CacheCommands::Clear => cache::clear(runtime::detect(cli.runtime)?)?,
+        },
     }
 
-    Ok(())
+    Ok(0)
 }
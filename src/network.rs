@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::port::{self, PortMapping};
+use crate::runtime::Runtime;
+
+/// Variable set in containers to the address the host is reached at
+pub(crate) const HOST_ADDR_VAR: &str = "JAIL_HOST_ADDR";
+
+/// How a jail's container is connected to the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// Share the host's network stack (ports need no publishing)
+    Host,
+    /// The runtime's default isolated network, with ports published explicitly
+    Bridge,
+    /// No network access at all
+    None,
+}
+
+impl NetworkMode {
+    /// Default when neither the jail nor the config sets a mode. Host networking
+    /// doesn't work through the macOS VM, so ports are published there instead.
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "macos") {
+            NetworkMode::Bridge
+        } else {
+            NetworkMode::Host
+        }
+    }
+
+    /// Runtime arguments for this mode and the jail's ports
+    pub fn args(&self, ports: &[PortMapping]) -> Vec<String> {
+        match self {
+            NetworkMode::Host => vec!["--network=host".to_string()],
+            // Leave the network to the runtime's default (bridge, or pasta/slirp for rootless Podman)
+            NetworkMode::Bridge => port::publish_args(ports)
+                .into_iter()
+                .flat_map(|arg| ["-p".to_string(), arg])
+                .collect(),
+            NetworkMode::None => vec!["--network=none".to_string()],
+        }
+    }
+
+    /// Runtime arguments making services on the host reachable from the container,
+    /// and the address to reach them at (none without a network)
+    pub fn host_access(&self, runtime: Runtime) -> Option<(Vec<String>, &'static str)> {
+        host_access(*self, runtime, cfg!(target_os = "linux"))
+    }
+}
+
+fn host_access(
+    mode: NetworkMode,
+    runtime: Runtime,
+    linux: bool,
+) -> Option<(Vec<String>, &'static str)> {
+    match (mode, runtime) {
+        (NetworkMode::None, _) => None,
+        // The container shares the host's network, so the host is localhost
+        (NetworkMode::Host, _) if linux => Some((Vec::new(), "localhost")),
+        // Docker Desktop resolves host.docker.internal by itself, Docker Engine on
+        // Linux only when told to
+        (_, Runtime::Docker) => Some((
+            vec![format!(
+                "--add-host={}:host-gateway",
+                runtime.host_gateway()
+            )],
+            runtime.host_gateway(),
+        )),
+        // Podman adds host.containers.internal to every container's /etc/hosts
+        (_, Runtime::Podman) => Some((Vec::new(), runtime.host_gateway())),
+    }
+}
+
+impl fmt::Display for NetworkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkMode::Host => write!(f, "host"),
+            NetworkMode::Bridge => write!(f, "bridge"),
+            NetworkMode::None => write!(f, "none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_args() {
+        let ports = ["3000".parse().unwrap(), "5353/udp".parse().unwrap()];
+        assert_eq!(NetworkMode::Host.args(&ports), ["--network=host"]);
+        assert_eq!(
+            NetworkMode::Bridge.args(&ports),
+            ["-p", "3000:3000", "-p", "5353:5353/udp"]
+        );
+        // No network means nothing to publish
+        assert_eq!(NetworkMode::None.args(&ports), ["--network=none"]);
+    }
+
+    #[test]
+    fn test_host_access() {
+        let docker = ["--add-host=host.docker.internal:host-gateway".to_string()];
+        assert_eq!(
+            host_access(NetworkMode::Bridge, Runtime::Docker, true),
+            Some((docker.to_vec(), "host.docker.internal"))
+        );
+        assert_eq!(
+            host_access(NetworkMode::Bridge, Runtime::Podman, true),
+            Some((Vec::new(), "host.containers.internal"))
+        );
+        assert_eq!(
+            host_access(NetworkMode::Host, Runtime::Docker, true),
+            Some((Vec::new(), "localhost"))
+        );
+        // Host networking on macOS is the VM's, not the Mac's
+        assert_eq!(
+            host_access(NetworkMode::Host, Runtime::Docker, false),
+            Some((docker.to_vec(), "host.docker.internal"))
+        );
+        assert_eq!(
+            host_access(NetworkMode::Host, Runtime::Podman, false),
+            Some((Vec::new(), "host.containers.internal"))
+        );
+        assert_eq!(host_access(NetworkMode::None, Runtime::Docker, true), None);
+    }
+}
@@ -0,0 +1,416 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::mpsc;
+
+static UI: AtomicU8 = AtomicU8::new(Ui::Silent as u8);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// What is written to the terminal. The library is silent, and never prompts,
+/// until the CLI picks one of the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Ui {
+    /// Nothing: results and failures are only returned
+    Silent = 0,
+    /// Warnings, results and prompts, but no progress lines; child process output
+    /// goes to log files (`--quiet`)
+    Quiet = 1,
+    /// Everything
+    Normal = 2,
+}
+
+/// Choose what is written to the terminal, once at startup
+pub fn set_ui(ui: Ui) {
+    UI.store(ui as u8, Ordering::Relaxed);
+}
+
+fn ui() -> Ui {
+    match UI.load(Ordering::Relaxed) {
+        0 => Ui::Silent,
+        1 => Ui::Quiet,
+        _ => Ui::Normal,
+    }
+}
+
+/// Echo every external command before running it
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// Whether progress lines and child process output are hidden
+pub(crate) fn is_quiet() -> bool {
+    ui() < Ui::Normal
+}
+
+/// Whether nothing at all is written to the terminal
+pub(crate) fn is_silent() -> bool {
+    ui() == Ui::Silent
+}
+
+/// Turn colored output on or off for this process: `--no-color` and `NO_COLOR`
+/// disable it, `CLICOLOR_FORCE` forces it, and otherwise it follows whether
+/// stdout is a terminal
+pub fn set_color(no_color: bool) {
+    let enabled = use_color(
+        no_color,
+        std::env::var("NO_COLOR").ok().as_deref(),
+        std::env::var("CLICOLOR_FORCE").ok().as_deref(),
+        std::io::stdout().is_terminal(),
+    );
+    colored::control::set_override(enabled);
+}
+
+/// Whether to color output, given `--no-color`, the `NO_COLOR` and `CLICOLOR_FORCE`
+/// variables and whether stdout is a terminal. Empty variables count as unset.
+fn use_color(
+    no_color: bool,
+    no_color_env: Option<&str>,
+    force_env: Option<&str>,
+    terminal: bool,
+) -> bool {
+    if no_color || no_color_env.is_some_and(|v| !v.is_empty()) {
+        return false;
+    }
+    if force_env.is_some_and(|v| !v.is_empty() && v != "0") {
+        return true;
+    }
+    terminal
+}
+
+/// Print a `→` progress line, unless quiet
+macro_rules! step {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            println!("{} {}", "→".blue().bold(), format!($($arg)*));
+        }
+    };
+}
+pub(crate) use step;
+
+/// Print a `!` warning to stderr, unless silent
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_silent() {
+            eprintln!("{} {}", "!".yellow().bold(), format!($($arg)*));
+        }
+    };
+}
+pub(crate) use warning;
+
+/// Print a `✓` line for a finished operation, unless silent
+macro_rules! done {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_silent() {
+            println!("{} {}", "✓".green().bold(), format!($($arg)*));
+        }
+    };
+}
+pub(crate) use done;
+
+/// Animated status line on stderr for waits without other output. Nothing is
+/// drawn when quiet or when stderr is not a terminal.
+pub(crate) struct Spinner {
+    message: String,
+    frame: usize,
+    drawn: bool,
+}
+
+impl Spinner {
+    const FRAMES: [&'static str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            frame: 0,
+            drawn: false,
+        }
+    }
+
+    /// Draw the next frame
+    pub fn tick(&mut self) {
+        if is_quiet() || !std::io::stderr().is_terminal() {
+            return;
+        }
+        let frame = Self::FRAMES[self.frame % Self::FRAMES.len()];
+        eprint!("\r{} {}", frame.blue().bold(), self.message);
+        let _ = std::io::stderr().flush();
+        self.frame += 1;
+        self.drawn = true;
+    }
+}
+
+impl Drop for Spinner {
+    /// Clear the line, so whatever comes next starts at its beginning
+    fn drop(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+
+/// Echoing of commands in verbose mode
+pub(crate) trait Traced {
+    /// Print the command to stderr when verbose
+    fn traced(&mut self) -> &mut Self;
+}
+
+impl Traced for Command {
+    fn traced(&mut self) -> &mut Self {
+        if VERBOSE.load(Ordering::Relaxed) {
+            eprintln!("{} {}", "$".dimmed(), render(self).dimmed());
+        }
+        self
+    }
+}
+
+/// Shell-like rendering of a command line
+pub(crate) fn render(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || "'\"$\\".contains(c)) {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Unless `ui` is normal, send a command's stdout and stderr to `log` instead of the terminal
+fn log_to(cmd: &mut Command, log: &Path, ui: Ui) -> Result<()> {
+    if ui == Ui::Normal {
+        return Ok(());
+    }
+    if let Some(dir) = log.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    let file = File::create(log).with_context(|| format!("Failed to create {}", log.display()))?;
+    cmd.stdout(file.try_clone()?).stderr(file);
+    Ok(())
+}
+
+/// When `ui` is quiet, print a failed command's log to stderr
+fn dump_log(log: &Path, ui: Ui) {
+    if ui != Ui::Quiet {
+        return;
+    }
+    if let Ok(content) = std::fs::read_to_string(log) {
+        eprint!("{}", content);
+        eprintln!("{} Full output in {}", "!".yellow().bold(), log.display());
+    }
+}
+
+/// Run a command to completion, logging its output to `log` when quiet
+pub(crate) fn run(cmd: &mut Command, log: &Path) -> Result<ExitStatus> {
+    run_in(ui(), cmd, log)
+}
+
+fn run_in(ui: Ui, cmd: &mut Command, log: &Path) -> Result<ExitStatus> {
+    log_to(cmd, log, ui)?;
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd
+        .traced()
+        .status()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        dump_log(log, ui);
+    }
+    Ok(status)
+}
+
+/// Lines of output kept to explain a failure
+const TAIL_LINES: usize = 50;
+
+/// Run a command to completion, writing `stdin` to it. Its output is copied line by
+/// line to `log` and, unless quiet, the terminal. Returns the exit status and the
+/// last lines of output.
+pub(crate) fn run_logged(
+    cmd: &mut Command,
+    stdin: Option<&[u8]>,
+    log: &Path,
+) -> Result<(ExitStatus, Vec<String>)> {
+    if let Some(dir) = log.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    let mut file =
+        File::create(log).with_context(|| format!("Failed to create {}", log.display()))?;
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .traced()
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+
+    let (sender, lines) = mpsc::channel();
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+    std::thread::scope(|scope| {
+        if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+            // Dropping the pipe afterwards closes the command's stdin
+            scope.spawn(move || pipe.write_all(input));
+        }
+        if let Some(pipe) = child.stdout.take() {
+            let sender = sender.clone();
+            scope.spawn(move || forward_lines(pipe, false, sender));
+        }
+        if let Some(pipe) = child.stderr.take() {
+            let sender = sender.clone();
+            scope.spawn(move || forward_lines(pipe, true, sender));
+        }
+        drop(sender);
+
+        for (is_stderr, line) in lines {
+            let _ = writeln!(file, "{}", line);
+            if !is_quiet() {
+                if is_stderr {
+                    eprintln!("{}", line);
+                } else {
+                    println!("{}", line);
+                }
+            }
+            if tail.len() == TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    });
+
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for {}", program))?;
+    Ok((status, tail.into()))
+}
+
+/// Send each line read from a pipe, tagged with whether it is stderr
+fn forward_lines(pipe: impl Read, is_stderr: bool, sender: mpsc::Sender<(bool, String)>) {
+    for line in BufReader::new(pipe)
+        .split(b'\n')
+        .map_while(|line| line.ok())
+    {
+        let line = String::from_utf8_lossy(&line).trim_end().to_string();
+        if sender.send((is_stderr, line)).is_err() {
+            break;
+        }
+    }
+}
+
+/// The lines of a failed command's output that explain the failure: those
+/// mentioning an error, or the last few when none do
+pub(crate) fn error_lines(output: &[String]) -> Vec<&str> {
+    let errors: Vec<&str> = output
+        .iter()
+        .map(String::as_str)
+        .filter(|line| line.to_lowercase().contains("error"))
+        .collect();
+    if !errors.is_empty() {
+        return errors;
+    }
+    let last = output.iter().rev().filter(|line| !line.trim().is_empty());
+    let mut lines: Vec<&str> = last.take(5).map(String::as_str).collect();
+    lines.reverse();
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_command() {
+        let mut cmd = Command::new("docker");
+        cmd.args(["run", "-e", "A=b c", "it's", ""]);
+        assert_eq!(render(&cmd), r#"docker run -e 'A=b c' 'it'\''s' ''"#);
+    }
+
+    #[test]
+    fn test_quiet_run_logs_output() {
+        let log = std::env::temp_dir()
+            .join(format!("jail-test-log-{}", std::process::id()))
+            .join("run.log");
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2"]);
+
+        for ui in [Ui::Silent, Ui::Quiet] {
+            let status = run_in(ui, &mut cmd, &log);
+            assert!(status.unwrap().success());
+            assert_eq!(std::fs::read_to_string(&log).unwrap(), "out\nerr\n");
+            std::fs::remove_dir_all(log.parent().unwrap()).unwrap();
+        }
+
+        // Otherwise the output goes where the command's already does
+        let status = run_in(
+            Ui::Normal,
+            cmd.stdout(Stdio::null()).stderr(Stdio::null()),
+            &log,
+        );
+        assert!(status.unwrap().success());
+        assert!(!log.exists());
+    }
+
+    #[test]
+    fn test_run_logged() {
+        let log = std::env::temp_dir()
+            .join(format!("jail-test-logged-{}", std::process::id()))
+            .join("build.log");
+
+        let (status, tail) = run_logged(
+            Command::new("sh").args(["-c", "cat; echo 'ERROR: no space' >&2; exit 1"]),
+            Some(b"FROM scratch\n"),
+            &log,
+        )
+        .unwrap();
+
+        // stdout and stderr are read concurrently, so their lines may interleave
+        // in either order
+        assert!(!status.success());
+        let mut tail = tail;
+        tail.sort();
+        assert_eq!(tail, ["ERROR: no space", "FROM scratch"]);
+        let logged = std::fs::read_to_string(&log).unwrap();
+        let mut logged: Vec<&str> = logged.lines().collect();
+        logged.sort();
+        assert_eq!(logged, ["ERROR: no space", "FROM scratch"]);
+        std::fs::remove_dir_all(log.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_error_lines() {
+        let output: Vec<String> = ["#1 load", "#2 ERROR: apt failed", "", "done"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(error_lines(&output), ["#2 ERROR: apt failed"]);
+
+        let output: Vec<String> = (1..=8).map(|i| i.to_string()).collect();
+        assert_eq!(error_lines(&output), ["4", "5", "6", "7", "8"]);
+    }
+
+    #[test]
+    fn test_use_color() {
+        assert!(use_color(false, None, None, true));
+        assert!(!use_color(false, None, None, false));
+        assert!(!use_color(true, None, None, true));
+        assert!(!use_color(false, Some("1"), None, true));
+        assert!(use_color(false, Some(""), None, true));
+        assert!(use_color(false, None, Some("1"), false));
+        assert!(!use_color(false, None, Some("0"), false));
+        // NO_COLOR and the flag win over CLICOLOR_FORCE
+        assert!(!use_color(false, Some("1"), Some("1"), true));
+        assert!(!use_color(true, None, Some("1"), true));
+    }
+}
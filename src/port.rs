@@ -0,0 +1,502 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+use std::process::Command;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+/// A port exposed from the container, e.g. `8080:3000` or `5353/udp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortMapping {
+    pub host: u16,
+    pub container: u16,
+    pub protocol: Protocol,
+}
+
+impl PortMapping {
+    /// Argument for the runtime's `-p` flag
+    pub fn publish_arg(&self) -> String {
+        self.to_string()
+    }
+
+    /// Human readable form, e.g. `8080->3000` or `5353->5353/udp`
+    pub fn display(&self) -> String {
+        let mut s = format!("{}->{}", self.host, self.container);
+        if self.protocol == Protocol::Udp {
+            s.push_str("/udp");
+        }
+        s
+    }
+}
+
+/// A port the runtime actually publishes, as listed by `<runtime> port <container>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Binding {
+    pub container: u16,
+    pub protocol: Protocol,
+    /// Address the port is bound to on the host, e.g. `0.0.0.0` or `127.0.0.1`
+    pub host_ip: String,
+    pub host: u16,
+}
+
+impl Binding {
+    /// Whether this publishes a port declared for the jail
+    pub fn publishes(&self, port: &PortMapping) -> bool {
+        (self.host, self.container, self.protocol) == (port.host, port.container, port.protocol)
+    }
+
+    /// Where the port is reachable from the host: an `http://` URL for TCP ports,
+    /// the address with `/udp` otherwise. Ports bound to all interfaces are
+    /// reachable on localhost, including through Docker Desktop's and Podman
+    /// machine's port forwarding on macOS.
+    pub fn url(&self) -> String {
+        let host = match self.host_ip.as_str() {
+            "" | "0.0.0.0" | "::" => "localhost".to_string(),
+            ip if ip.contains(':') => format!("[{}]", ip),
+            ip => ip.to_string(),
+        };
+        match self.protocol {
+            Protocol::Tcp => format!("http://{}:{}", host, self.host),
+            Protocol::Udp => format!("{}:{}/udp", host, self.host),
+        }
+    }
+}
+
+/// Parse `port` output, lines like `3000/tcp -> 0.0.0.0:3000`. Ports bound on both
+/// IPv4 and IPv6 are listed once.
+pub(crate) fn parse_bindings(output: &str) -> Vec<Binding> {
+    let mut bindings: Vec<Binding> = Vec::new();
+    for line in output.lines() {
+        let Some((container, host)) = line.trim().split_once(" -> ") else {
+            continue;
+        };
+        let Ok(PortMapping {
+            container,
+            protocol,
+            ..
+        }) = container.parse::<PortMapping>()
+        else {
+            continue;
+        };
+        let Some((host_ip, host)) = host.rsplit_once(':') else {
+            continue;
+        };
+        let Ok(host) = host.parse::<u16>() else {
+            continue;
+        };
+        let host_ip = host_ip.trim_start_matches('[').trim_end_matches(']');
+        let wildcard = matches!(host_ip, "0.0.0.0" | "::");
+        let duplicate = bindings.iter().any(|b| {
+            (b.container, b.protocol, b.host) == (container, protocol, host)
+                && (b.host_ip == host_ip
+                    || wildcard && matches!(b.host_ip.as_str(), "0.0.0.0" | "::"))
+        });
+        if !duplicate {
+            bindings.push(Binding {
+                container,
+                protocol,
+                host_ip: host_ip.to_string(),
+                host,
+            });
+        }
+    }
+    bindings
+}
+
+/// Whether a port is already taken on the host, checked by binding it on all
+/// interfaces as the runtime will
+pub(crate) fn in_use(port: &PortMapping) -> bool {
+    let addr = (Ipv4Addr::UNSPECIFIED, port.host);
+    let bound = match port.protocol {
+        Protocol::Tcp => TcpListener::bind(addr).map(drop),
+        Protocol::Udp => UdpSocket::bind(addr).map(drop),
+    };
+    // Other errors, such as privileged ports, are left for the runtime to report
+    matches!(bound, Err(e) if e.kind() == ErrorKind::AddrInUse)
+}
+
+/// The process holding a host port, e.g. `node (pid 4242)`, when lsof or ss can tell
+pub(crate) fn holder(port: &PortMapping) -> Option<String> {
+    let (lsof_filter, ss_flags) = match port.protocol {
+        Protocol::Tcp => (format!("-iTCP:{}", port.host), "-Htlnp"),
+        Protocol::Udp => (format!("-iUDP:{}", port.host), "-Hulnp"),
+    };
+    let mut lsof = Command::new("lsof");
+    lsof.args(["-nP", "-Fpc", &lsof_filter]);
+    if port.protocol == Protocol::Tcp {
+        lsof.arg("-sTCP:LISTEN");
+    }
+    if let Some(holder) = command_output(&mut lsof).and_then(|out| parse_lsof(&out)) {
+        return Some(holder);
+    }
+
+    let mut ss = Command::new("ss");
+    ss.args([ss_flags, "sport", "=", &format!(":{}", port.host)]);
+    command_output(&mut ss).and_then(|out| parse_ss(&out))
+}
+
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The first process in `lsof -Fpc` output, made of `p<pid>` and `c<command>` lines
+fn parse_lsof(output: &str) -> Option<String> {
+    let pid = output.lines().find_map(|line| line.strip_prefix('p'))?;
+    let command = output.lines().find_map(|line| line.strip_prefix('c'))?;
+    Some(format!("{} (pid {})", command, pid))
+}
+
+/// The first process in `ss -p` output, e.g. `users:(("node",pid=4242,fd=21))`.
+/// Other users' processes are only listed when run as root.
+fn parse_ss(output: &str) -> Option<String> {
+    let users = output.split_once("users:((\"")?.1;
+    let (command, rest) = users.split_once('"')?;
+    let pid = rest.split_once("pid=")?.1;
+    let pid = pid.split(|c: char| !c.is_ascii_digit()).next()?;
+    Some(format!("{} (pid {})", command, pid))
+}
+
+/// Split a `/tcp` or `/udp` suffix off a port specification
+fn split_protocol(s: &str) -> Result<(&str, Protocol), String> {
+    match s.split_once('/') {
+        Some((ports, "tcp")) => Ok((ports, Protocol::Tcp)),
+        Some((ports, "udp")) => Ok((ports, Protocol::Udp)),
+        Some((_, proto)) => Err(format!("unknown protocol '{}' (use tcp or udp)", proto)),
+        None => Ok((s, Protocol::Tcp)),
+    }
+}
+
+fn parse_port(p: &str) -> Result<u16, String> {
+    match p.parse::<u16>() {
+        Ok(0) | Err(_) => Err(format!("invalid port '{}'", p)),
+        Ok(port) => Ok(port),
+    }
+}
+
+impl FromStr for PortMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ports, protocol) = split_protocol(s)?;
+        let (host, container) = match ports.split_once(':') {
+            Some((host, container)) => (parse_port(host)?, parse_port(container)?),
+            None => {
+                let port = parse_port(ports)?;
+                (port, port)
+            }
+        };
+
+        Ok(Self {
+            host,
+            container,
+            protocol,
+        })
+    }
+}
+
+/// Ports given on the command line: a single mapping, or an inclusive range such
+/// as `9000-9010`, `9000-9010:8000-8010` or `9000-9010/udp`, expanded into one
+/// mapping per port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortRange(Vec<PortMapping>);
+
+impl PortRange {
+    /// Most ports a range may cover, as each becomes a mapping of its own
+    const MAX_LEN: usize = 1000;
+}
+
+/// Parse `start-end` or a single port as an inclusive range
+fn parse_range(s: &str) -> Result<(u16, u16), String> {
+    let Some((start, end)) = s.split_once('-') else {
+        let port = parse_port(s)?;
+        return Ok((port, port));
+    };
+    let (start, end) = (parse_port(start)?, parse_port(end)?);
+    if start > end {
+        return Err(format!("invalid port range '{}' (start is after end)", s));
+    }
+    Ok((start, end))
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ports, protocol) = split_protocol(s)?;
+        let (host, container) = match ports.split_once(':') {
+            Some((host, container)) => (parse_range(host)?, parse_range(container)?),
+            None => {
+                let range = parse_range(ports)?;
+                (range, range)
+            }
+        };
+
+        let len = usize::from(host.1 - host.0) + 1;
+        if usize::from(container.1 - container.0) + 1 != len {
+            return Err(format!("port ranges in '{}' have different lengths", s));
+        }
+        if len > Self::MAX_LEN {
+            return Err(format!(
+                "port range '{}' is too large (at most {} ports)",
+                s,
+                Self::MAX_LEN
+            ));
+        }
+
+        Ok(Self(
+            (host.0..=host.1)
+                .zip(container.0..=container.1)
+                .map(|(host, container)| PortMapping {
+                    host,
+                    container,
+                    protocol,
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// The mappings of ports given on the command line, without duplicates
+pub fn expand(ranges: Vec<PortRange>) -> Vec<PortMapping> {
+    let mut ports: Vec<PortMapping> = Vec::new();
+    for port in ranges.into_iter().flat_map(|range| range.0) {
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+    ports
+}
+
+/// Group ports into runs of consecutive host ports mapped to consecutive
+/// container ports, as the first mapping of each run and its length
+fn runs(ports: &[PortMapping]) -> Vec<(PortMapping, u16)> {
+    let mut runs: Vec<(PortMapping, u16)> = Vec::new();
+    for port in ports {
+        if let Some((first, len)) = runs.last_mut() {
+            let follows = first.protocol == port.protocol
+                && u32::from(first.host) + u32::from(*len) == u32::from(port.host)
+                && u32::from(first.container) + u32::from(*len) == u32::from(port.container);
+            if follows {
+                *len += 1;
+                continue;
+            }
+        }
+        runs.push((*port, 1));
+    }
+    runs
+}
+
+/// Arguments for the runtime's `-p` flag, with consecutive ports as one range
+pub(crate) fn publish_args(ports: &[PortMapping]) -> Vec<String> {
+    runs(ports)
+        .into_iter()
+        .map(|(first, len)| match len {
+            1 => first.publish_arg(),
+            _ => {
+                let mut arg = format!(
+                    "{}-{}:{}-{}",
+                    first.host,
+                    first.host + (len - 1),
+                    first.container,
+                    first.container + (len - 1)
+                );
+                if first.protocol == Protocol::Udp {
+                    arg.push_str("/udp");
+                }
+                arg
+            }
+        })
+        .collect()
+}
+
+/// Human readable forms of ports, with consecutive ports as one range, e.g. `9000-9010->9000-9010`
+pub(crate) fn display(ports: &[PortMapping]) -> Vec<String> {
+    runs(ports)
+        .into_iter()
+        .map(|(first, len)| match len {
+            1 => first.display(),
+            _ => {
+                let mut s = format!(
+                    "{}-{}->{}-{}",
+                    first.host,
+                    first.host + (len - 1),
+                    first.container,
+                    first.container + (len - 1)
+                );
+                if first.protocol == Protocol::Udp {
+                    s.push_str("/udp");
+                }
+                s
+            }
+        })
+        .collect()
+}
+
+impl fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.container)?;
+        if self.protocol == Protocol::Udp {
+            write!(f, "/udp")?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for PortMapping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PortMapping {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Older jail.toml files store plain port numbers
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Port(u16),
+            Mapping(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Port(port) => Ok(Self {
+                host: port,
+                container: port,
+                protocol: Protocol::Tcp,
+            }),
+            Raw::Mapping(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_port_mapping() {
+        let port: PortMapping = "3000".parse().unwrap();
+        assert_eq!((port.host, port.container), (3000, 3000));
+        assert_eq!(port.protocol, Protocol::Tcp);
+
+        let port: PortMapping = "8080:3000".parse().unwrap();
+        assert_eq!((port.host, port.container), (8080, 3000));
+
+        let port: PortMapping = "5353/udp".parse().unwrap();
+        assert_eq!(port.protocol, Protocol::Udp);
+        assert_eq!(port.publish_arg(), "5353:5353/udp");
+        assert_eq!(port.display(), "5353->5353/udp");
+
+        assert!("0".parse::<PortMapping>().is_err());
+        assert!("70000".parse::<PortMapping>().is_err());
+        assert!("80/sctp".parse::<PortMapping>().is_err());
+        assert!("a:b".parse::<PortMapping>().is_err());
+    }
+
+    #[test]
+    fn test_parse_port_range() {
+        let range: PortRange = "9000-9002".parse().unwrap();
+        assert_eq!(range.0.len(), 3);
+        assert_eq!(range.0[2], "9002".parse().unwrap());
+
+        let range: PortRange = "9000-9001:8000-8001/udp".parse().unwrap();
+        assert_eq!(range.0[1], "9001:8001/udp".parse().unwrap());
+
+        let single: PortRange = "8080:3000".parse().unwrap();
+        assert_eq!(single.0, ["8080:3000".parse().unwrap()]);
+
+        assert!("9010-9000".parse::<PortRange>().is_err());
+        assert!("0-10".parse::<PortRange>().is_err());
+        assert!("9000-70000".parse::<PortRange>().is_err());
+        assert!("9000-9010:8000-8001".parse::<PortRange>().is_err());
+        assert!("1-65535".parse::<PortRange>().is_err());
+        assert!("9000-9001/sctp".parse::<PortRange>().is_err());
+    }
+
+    #[test]
+    fn test_expand_and_publish_ranges() {
+        let ranges = ["9000-9002", "9001", "51820/udp", "9003"]
+            .map(|s| s.parse::<PortRange>().unwrap())
+            .to_vec();
+        let ports = expand(ranges);
+        assert_eq!(ports.len(), 5);
+        assert_eq!(
+            publish_args(&ports),
+            ["9000-9002:9000-9002", "51820:51820/udp", "9003:9003"]
+        );
+        assert_eq!(
+            display(&ports),
+            ["9000-9002->9000-9002", "51820->51820/udp", "9003->9003"]
+        );
+
+        // Ranges of UDP ports keep their protocol
+        let ports = expand(vec!["5000-5001/udp".parse().unwrap()]);
+        assert_eq!(publish_args(&ports), ["5000-5001:5000-5001/udp"]);
+    }
+
+    #[test]
+    fn test_parse_bindings() {
+        let output = "3000/tcp -> 0.0.0.0:8080\n3000/tcp -> [::]:8080\n\
+                      5353/udp -> 127.0.0.1:5353\n9229/tcp -> [::1]:9229\n";
+        let bindings = parse_bindings(output);
+        assert_eq!(bindings.len(), 3);
+        assert!(bindings[0].publishes(&"8080:3000".parse().unwrap()));
+        assert!(!bindings[0].publishes(&"3000".parse().unwrap()));
+        let urls: Vec<String> = bindings.iter().map(Binding::url).collect();
+        assert_eq!(
+            urls,
+            [
+                "http://localhost:8080",
+                "127.0.0.1:5353/udp",
+                "http://[::1]:9229"
+            ]
+        );
+        assert!(parse_bindings("").is_empty());
+    }
+
+    #[test]
+    fn test_in_use() {
+        // Only ports held for the whole test are checked: a port released by the
+        // test could be taken by another process before it's checked again
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let taken = listener.local_addr().unwrap().port();
+        assert!(in_use(&taken.to_string().parse().unwrap()));
+
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let taken = socket.local_addr().unwrap().port();
+        assert!(in_use(&format!("{}/udp", taken).parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_holder() {
+        assert_eq!(
+            parse_lsof("p4242\ncnode\nf21\n").as_deref(),
+            Some("node (pid 4242)")
+        );
+        assert_eq!(parse_lsof(""), None);
+
+        let ss = "LISTEN 0 511 *:3000 *:* users:((\"node\",pid=4242,fd=21))\n";
+        assert_eq!(parse_ss(ss).as_deref(), Some("node (pid 4242)"));
+        assert_eq!(parse_ss("LISTEN 0 511 *:3000 *:*\n"), None);
+    }
+
+    #[test]
+    fn test_deserialize_legacy_ports() {
+        #[derive(Deserialize)]
+        struct Ports {
+            ports: Vec<PortMapping>,
+        }
+
+        let parsed: Ports = toml::from_str(r#"ports = [3000, "8080:80/udp"]"#).unwrap();
+        assert_eq!(parsed.ports[0], "3000:3000".parse().unwrap());
+        assert_eq!(parsed.ports[1], "8080:80/udp".parse().unwrap());
+    }
+}
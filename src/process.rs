@@ -0,0 +1,180 @@
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::OnceLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::output::render;
+
+/// Seconds a runtime query may take when command_timeout isn't set
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Why a command bounded by the timeout produced no result
+#[derive(Debug)]
+pub enum RunError {
+    /// The command couldn't be started or waited for
+    Failed(std::io::Error),
+    /// The command ran too long and was killed
+    TimedOut { command: String, after: Duration },
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Failed(e) => write!(f, "{}", e),
+            RunError::TimedOut { command, after } => write!(
+                f,
+                "'{}' did not finish within {}s and was stopped. The container runtime \
+                 may be hung; on slow machines, raise command_timeout in config.toml.",
+                command,
+                after.as_secs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunError::Failed(e) => Some(e),
+            RunError::TimedOut { .. } => None,
+        }
+    }
+}
+
+/// Time limit for runtime queries, from command_timeout in config (0 disables it)
+fn timeout() -> Option<Duration> {
+    static TIMEOUT: OnceLock<Option<Duration>> = OnceLock::new();
+    *TIMEOUT.get_or_init(|| {
+        let secs = crate::config::load()
+            .ok()
+            .and_then(|config| config.command_timeout)
+            .unwrap_or(DEFAULT_TIMEOUT);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    })
+}
+
+/// Running short runtime queries with a time limit, so a hung daemon fails the
+/// command instead of hanging it. Interactive sessions, builds and transfers
+/// of images or files keep using the unbounded `output()`/`status()`.
+pub(crate) trait Timed {
+    /// Like `output()`, killing the command once it runs longer than the timeout
+    fn timed_output(&mut self) -> Result<Output, RunError>;
+    /// Like `output()`, killing the command once it runs longer than `limit`
+    fn output_within(&mut self, limit: Duration) -> Result<Output, RunError>;
+    /// Like `status()`, killing the command once it runs longer than the timeout
+    fn timed_status(&mut self) -> Result<ExitStatus, RunError>;
+}
+
+impl Timed for Command {
+    fn timed_output(&mut self) -> Result<Output, RunError> {
+        match timeout() {
+            Some(limit) => self.output_within(limit),
+            None => self.output().map_err(RunError::Failed),
+        }
+    }
+
+    fn output_within(&mut self, limit: Duration) -> Result<Output, RunError> {
+        let mut child = self
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(RunError::Failed)?;
+
+        // Drain the pipes while waiting, so a chatty command can't block on a full pipe
+        let stdout = child.stdout.take().map(read_to_end);
+        let stderr = child.stderr.take().map(read_to_end);
+        let status = wait_within(&mut child, limit, self)?;
+
+        let collect = |reader: Option<JoinHandle<Vec<u8>>>| {
+            reader
+                .and_then(|reader| reader.join().ok())
+                .unwrap_or_default()
+        };
+        Ok(Output {
+            status,
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        })
+    }
+
+    fn timed_status(&mut self) -> Result<ExitStatus, RunError> {
+        let Some(limit) = timeout() else {
+            return self.status().map_err(RunError::Failed);
+        };
+        let mut child = self.spawn().map_err(RunError::Failed)?;
+        wait_within(&mut child, limit, self)
+    }
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// Wait for a child, killing it once `limit` has passed
+fn wait_within(child: &mut Child, limit: Duration, cmd: &Command) -> Result<ExitStatus, RunError> {
+    let deadline = Instant::now() + limit;
+    // Most queries finish within milliseconds, so start polling quickly
+    let mut delay = Duration::from_millis(1);
+
+    loop {
+        if let Some(status) = child.try_wait().map_err(RunError::Failed)? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(RunError::TimedOut {
+                command: render(cmd),
+                after: limit,
+            });
+        }
+        std::thread::sleep(delay);
+        delay = (delay * 2).min(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_within() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let mut child = cmd
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let status = wait_within(&mut child, Duration::from_secs(10), &cmd).unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        let mut cmd = Command::new("sleep");
+        cmd.arg("10");
+        let mut child = cmd.spawn().unwrap();
+        let start = Instant::now();
+        let err = wait_within(&mut child, Duration::from_millis(100), &cmd).unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(matches!(err, RunError::TimedOut { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("'sleep 10' did not finish within 0s"));
+    }
+
+    #[test]
+    fn test_timed_output_captures() {
+        let output = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .timed_output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+}
@@ -1,8 +1,15 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use dialoguer::{theme::ColorfulTheme, Confirm};
 use serde::{Deserialize, Serialize};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use crate::output::{warning, Spinner, Traced};
+use crate::process::Timed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum Runtime {
     Podman,
@@ -19,63 +26,253 @@ impl Runtime {
 
     /// Check if this runtime is available and working
     pub fn is_available(&self) -> bool {
+        self.availability() == Availability::Available
+    }
+
+    /// Client version, e.g. "Docker version 27.0.3, build 7d4bcd8"
+    pub fn version(&self) -> Option<String> {
+        let output = Command::new(self.command())
+            .arg("--version")
+            .traced()
+            .timed_output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let line = stdout.lines().next()?.trim();
+        (output.status.success() && !line.is_empty()).then(|| line.to_string())
+    }
+
+    /// Check if this runtime is installed, and if so whether it responds
+    pub(crate) fn availability(&self) -> Availability {
         let cmd = self.command();
         if which::which(cmd).is_err() {
-            return false;
+            return Availability::Missing;
         }
 
         // Check if the runtime is actually working
-        Command::new(cmd)
+        let output = Command::new(cmd)
             .args(["info"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+            .stdin(std::process::Stdio::null())
+            .traced()
+            .timed_output();
+        match output {
+            Ok(output) if output.status.success() => Availability::Available,
+            Ok(output) => {
+                Availability::NotResponding(error_summary(&String::from_utf8_lossy(&output.stderr)))
+            }
+            Err(e) => Availability::NotResponding(e.to_string()),
+        }
     }
 
-    /// Get SSH agent socket mount arguments for this runtime
-    pub fn ssh_agent_mount(&self) -> Option<Vec<String>> {
-        match self {
-            Runtime::Docker => {
-                // Docker Desktop on macOS uses a special path
-                if cfg!(target_os = "macos") {
-                    Some(vec![
-                        "-v".to_string(),
-                        "/run/host-services/ssh-auth.sock:/run/ssh.sock:ro".to_string(),
-                        "-e".to_string(),
-                        "SSH_AUTH_SOCK=/run/ssh.sock".to_string(),
-                    ])
-                } else if let Ok(sock) = std::env::var("SSH_AUTH_SOCK") {
-                    Some(vec![
-                        "-v".to_string(),
-                        format!("{}:/run/ssh.sock:ro", sock),
-                        "-e".to_string(),
-                        "SSH_AUTH_SOCK=/run/ssh.sock".to_string(),
-                    ])
-                } else {
-                    None
-                }
+    /// How to get this runtime's daemon (or Podman machine) running
+    pub fn start_hint(&self) -> &'static str {
+        match (self, std::env::consts::OS) {
+            (Runtime::Docker, "linux") => {
+                "Start it with 'sudo systemctl start docker', or open Docker Desktop."
             }
+            (Runtime::Docker, _) => "Start Docker Desktop and wait for it to finish starting.",
+            (Runtime::Podman, "linux") => "Run 'podman info' for details.",
+            (Runtime::Podman, _) => "Start the Podman machine with 'podman machine start'.",
+        }
+    }
+
+    /// Hostname containers use to reach the host
+    pub fn host_gateway(&self) -> &'static str {
+        match self {
+            Runtime::Podman => "host.containers.internal",
+            Runtime::Docker => "host.docker.internal",
+        }
+    }
+
+    /// Whether containers run without root on the host, so container UIDs map to
+    /// the user's subordinate IDs (rootless Podman)
+    pub fn is_rootless(&self) -> bool {
+        match self {
+            Runtime::Docker => false,
+            Runtime::Podman => Command::new("podman")
+                .args(["info", "--format", "json"])
+                .traced()
+                .timed_output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| parse_rootless(&String::from_utf8_lossy(&output.stdout)))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Path of the runtime's API socket as containers see it, for mounting into jails
+    /// that drive the host's runtime (`--docker-socket`)
+    pub fn socket_path(&self) -> Result<String> {
+        match self {
+            // Docker Desktop forwards this path to its daemon, whatever the host path is
+            Runtime::Docker if cfg!(target_os = "macos") => Ok(DOCKER_SOCKET.to_string()),
+            Runtime::Docker => Ok(std::env::var("DOCKER_HOST")
+                .ok()
+                .and_then(|host| host.strip_prefix("unix://").map(String::from))
+                .unwrap_or_else(|| DOCKER_SOCKET.to_string())),
+            // Rootless Podman listens under $XDG_RUNTIME_DIR, rootful under /run/podman.
+            // Inside a Podman machine, the path is the VM's, where containers run.
             Runtime::Podman => {
-                // On macOS, Podman runs in a VM and can't directly mount host Unix sockets
-                // SSH agent forwarding requires special Podman machine configuration
-                if cfg!(target_os = "macos") {
-                    None
-                } else if let Ok(sock) = std::env::var("SSH_AUTH_SOCK") {
-                    // On Linux, Podman can mount the SSH socket directly
-                    Some(vec![
-                        "-v".to_string(),
-                        format!("{}:/run/ssh.sock:ro", sock),
-                        "-e".to_string(),
-                        "SSH_AUTH_SOCK=/run/ssh.sock".to_string(),
+                let output = Command::new("podman")
+                    .args([
+                        "info",
+                        "--format",
+                        "{{.Host.RemoteSocket.Path}} {{.Host.RemoteSocket.Exists}}",
                     ])
-                } else {
-                    None
+                    .traced()
+                    .timed_output()
+                    .context("Failed to query the Podman socket")?;
+                let info = String::from_utf8_lossy(&output.stdout);
+                match parse_podman_socket(&info) {
+                    Some((path, true)) => Ok(path),
+                    Some((path, false)) => bail!(
+                        "The Podman socket {} is not running. Enable it with '{}'",
+                        path,
+                        if self.is_rootless() {
+                            "systemctl --user enable --now podman.socket"
+                        } else {
+                            "sudo systemctl enable --now podman.socket"
+                        }
+                    ),
+                    None => bail!("Podman didn't report its socket: {}", info.trim()),
                 }
             }
         }
     }
+
+    /// Get SSH agent socket mount arguments for this runtime. With `relabel`, the
+    /// socket is shared-labeled (`:z`) so SELinux lets the container use it while
+    /// the host's agent keeps working.
+    pub fn ssh_agent_mount(&self, relabel: bool) -> Option<Vec<String>> {
+        let relabel = relabel.then_some(Relabel::Shared);
+        let sock = match self {
+            // Docker Desktop on macOS uses a special path
+            Runtime::Docker if cfg!(target_os = "macos") => {
+                "/run/host-services/ssh-auth.sock".to_string()
+            }
+            // On macOS, Podman runs in a VM and can't directly mount host Unix sockets
+            // SSH agent forwarding requires special Podman machine configuration
+            Runtime::Podman if cfg!(target_os = "macos") => return None,
+            // On Linux, both can mount the SSH socket directly
+            _ => std::env::var("SSH_AUTH_SOCK").ok()?,
+        };
+        Some(vec![
+            "-v".to_string(),
+            volume_arg(&sock, "/run/ssh.sock", true, relabel),
+            "-e".to_string(),
+            "SSH_AUTH_SOCK=/run/ssh.sock".to_string(),
+        ])
+    }
+}
+
+/// How a bind mount is relabeled for SELinux
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Relabel {
+    /// `:Z`, usable only by this container
+    Private,
+    /// `:z`, usable by any container and still by the host
+    Shared,
+}
+
+/// Argument for the runtime's `-v` flag binding `host` to `container`
+pub(crate) fn volume_arg(
+    host: &str,
+    container: &str,
+    read_only: bool,
+    relabel: Option<Relabel>,
+) -> String {
+    let mut options = Vec::new();
+    if read_only {
+        options.push("ro");
+    }
+    match relabel {
+        Some(Relabel::Private) => options.push("Z"),
+        Some(Relabel::Shared) => options.push("z"),
+        None => {}
+    }
+
+    let mut arg = format!("{}:{}", host, container);
+    if !options.is_empty() {
+        arg.push(':');
+        arg.push_str(&options.join(","));
+    }
+    arg
+}
+
+/// Default Docker daemon socket
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// Socket path and whether it exists, from `podman info` formatted as `{path} {exists}`
+fn parse_podman_socket(info: &str) -> Option<(String, bool)> {
+    let (path, exists) = info.trim().rsplit_once(' ')?;
+    let path = path.strip_prefix("unix://").unwrap_or(path);
+    if path.is_empty() {
+        return None;
+    }
+    Some((path.to_string(), exists == "true"))
+}
+
+/// The rootless flag in `podman info --format json` output
+fn parse_rootless(info_json: &str) -> Option<bool> {
+    let info: serde_json::Value = serde_json::from_str(info_json).ok()?;
+    info["host"]["security"]["rootless"].as_bool()
+}
+
+/// Real UID and GID of the user running jail (Linux only)
+pub(crate) fn host_ids() -> Option<(u32, u32)> {
+    parse_host_ids(&std::fs::read_to_string("/proc/self/status").ok()?)
+}
+
+/// The real UID and GID from the `Uid:` and `Gid:` lines of /proc/self/status
+fn parse_host_ids(status: &str) -> Option<(u32, u32)> {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    };
+    Some((field("Uid:")?, field("Gid:")?))
+}
+
+/// Whether SELinux is enforcing on this host, in which case bind mounts are
+/// unreadable in containers unless relabeled
+pub(crate) fn selinux_enforcing() -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    if let Ok(enforce) = std::fs::read_to_string("/sys/fs/selinux/enforce") {
+        return enforce.trim() == "1";
+    }
+    // selinuxfs may be mounted elsewhere
+    Command::new("getenforce")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "Enforcing")
+}
+
+/// Whether a runtime can be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Availability {
+    Available,
+    /// The command isn't installed
+    Missing,
+    /// Installed, but the daemon (or Podman machine) didn't answer, with the reason given
+    NotResponding(String),
+}
+
+/// The reason from a failed `info`'s stderr, without the error prefix
+fn error_summary(stderr: &str) -> String {
+    stderr
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| {
+            line.trim_start_matches("ERROR: ")
+                .trim_start_matches("Error: ")
+                .to_string()
+        })
+        .unwrap_or_else(|| "no response".to_string())
 }
 
 impl std::fmt::Display for Runtime {
@@ -110,28 +307,256 @@ fn install_instructions() -> &'static str {
     }
 }
 
-/// Detect the best available runtime, preferring Podman
-pub fn detect() -> Result<Runtime> {
-    // Check for config override first
-    if let Some(runtime) = crate::config::get_runtime_override()? {
-        if runtime.is_available() {
-            return Ok(runtime);
-        }
+static NO_AUTO_START: AtomicBool = AtomicBool::new(false);
+
+/// Disable starting a stopped Podman machine (`--no-auto-start`)
+pub fn set_no_auto_start(no_auto_start: bool) {
+    NO_AUTO_START.store(no_auto_start, Ordering::Relaxed);
+}
+
+/// Entry of `podman machine list --format json`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PodmanMachine {
+    name: String,
+    #[serde(default)]
+    default: bool,
+    #[serde(default)]
+    running: bool,
+    #[serde(default)]
+    starting: bool,
+}
+
+/// Whether a Podman machine is on its way up
+fn machine_starting(list_json: &str) -> bool {
+    serde_json::from_str::<Vec<PodmanMachine>>(list_json)
+        .is_ok_and(|machines| machines.iter().any(|m| m.starting))
+}
+
+/// Name of the Podman machine to start, if there are machines and none is running
+fn stopped_machine(list_json: &str) -> Option<String> {
+    let machines: Vec<PodmanMachine> = serde_json::from_str(list_json).ok()?;
+    if machines.iter().any(|m| m.running || m.starting) {
+        return None;
+    }
+    machines
+        .iter()
+        .find(|m| m.default)
+        .or(machines.first())
+        .map(|m| m.name.clone())
+}
+
+/// The Podman machine in use (the default one, or the first) and whether it is running
+fn machine_summary(list_json: &str) -> Option<(String, bool)> {
+    let machines: Vec<PodmanMachine> = serde_json::from_str(list_json).ok()?;
+    machines
+        .iter()
+        .find(|m| m.running)
+        .or(machines.iter().find(|m| m.default))
+        .or(machines.first())
+        .map(|m| (m.name.clone(), m.running))
+}
+
+/// Name of the Podman machine and whether it is running, or None when there is none
+pub(crate) fn podman_machine() -> Result<Option<(String, bool)>> {
+    let output = Command::new("podman")
+        .args(["machine", "list", "--format", "json"])
+        .traced()
+        .timed_output()
+        .context("Failed to list Podman machines")?;
+    if !output.status.success() {
         bail!(
-            "Configured runtime '{}' is not available or not working",
-            runtime
+            "Failed to list Podman machines: {}",
+            error_summary(&String::from_utf8_lossy(&output.stderr))
+        );
+    }
+    Ok(machine_summary(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Find a stopped Podman machine (the VM Podman needs on macOS)
+fn stopped_podman_machine() -> Option<String> {
+    let output = Command::new("podman")
+        .args(["machine", "list", "--format", "json"])
+        .traced()
+        .timed_output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    stopped_machine(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Offer to start a stopped Podman machine, returning true once Podman is usable
+fn start_podman_machine() -> Result<bool> {
+    if which::which("podman").is_err() {
+        return Ok(false);
+    }
+    let Some(machine) = stopped_podman_machine() else {
+        return Ok(false);
+    };
+
+    if NO_AUTO_START.load(Ordering::Relaxed) || !crate::jail::is_interactive() {
+        warning!(
+            "Podman machine '{}' is stopped. Run '{}' to start it.",
+            machine,
+            "podman machine start".cyan()
         );
+        return Ok(false);
+    }
+
+    let start = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Podman machine is stopped, start it now?")
+        .default(true)
+        .interact()?;
+    if !start {
+        // Don't ask again if the runtime is detected more than once
+        NO_AUTO_START.store(true, Ordering::Relaxed);
+        return Ok(false);
+    }
+
+    let status = Command::new("podman")
+        .args(["machine", "start", &machine])
+        .traced()
+        .status()
+        .context("Failed to run podman machine start")?;
+    if !status.success() {
+        bail!("Failed to start Podman machine '{}'", machine);
+    }
+
+    // The VM can take a moment to accept connections after start returns
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while !Runtime::Podman.is_available() {
+        if Instant::now() > deadline {
+            bail!("Podman machine '{}' started but is not responding", machine);
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+
+    Ok(true)
+}
+
+/// Seconds to wait for a starting daemon when daemon_wait isn't set
+const DEFAULT_DAEMON_WAIT: u64 = 20;
+
+/// Whether a runtime that isn't responding looks like it's on its way up: Docker
+/// Desktop's backend or the docker systemd unit still starting, or a Podman machine booting
+fn is_starting(runtime: Runtime) -> bool {
+    let stdout = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .stderr(std::process::Stdio::null())
+            .traced()
+            .timed_output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    match runtime {
+        Runtime::Docker => {
+            stdout("pgrep", &["-f", "com.docker.backend"]).is_some()
+                || stdout(
+                    "systemctl",
+                    &["show", "-p", "ActiveState", "--value", "docker"],
+                )
+                .is_some_and(|state| state == "activating")
+        }
+        Runtime::Podman => stdout("podman", &["machine", "list", "--format", "json"])
+            .is_some_and(|list| machine_starting(&list)),
+    }
+}
+
+/// Wait with backoff for a runtime that is starting, returning whether it came up in time
+fn wait_until_started(runtime: Runtime) -> Result<bool> {
+    if !is_starting(runtime) {
+        return Ok(false);
+    }
+
+    let wait = crate::config::load()?
+        .daemon_wait
+        .unwrap_or(DEFAULT_DAEMON_WAIT);
+    let deadline = Instant::now() + Duration::from_secs(wait);
+    let mut spinner = Spinner::new(format!("Waiting for {} to start...", runtime));
+    let mut delay = Duration::from_millis(500);
+    let mut next_check = Instant::now() + delay;
+
+    loop {
+        spinner.tick();
+        std::thread::sleep(Duration::from_millis(100));
+        if Instant::now() < next_check {
+            continue;
+        }
+        if runtime.is_available() {
+            return Ok(true);
+        }
+        if Instant::now() >= deadline {
+            return Ok(false);
+        }
+        delay = (delay * 2).min(Duration::from_secs(4));
+        next_check = Instant::now() + delay;
+    }
+}
+
+/// Error for a runtime that is installed but can't be reached
+fn not_responding(runtime: Runtime, reason: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{} is installed but not responding: {}\n\n{}",
+        runtime,
+        reason,
+        runtime.start_hint()
+    )
+}
+
+/// Detect the best available runtime, preferring Podman, unless `requested`
+/// (`--runtime`), the environment or config pick one. Runtimes that are still
+/// starting up are waited for.
+pub fn detect(requested: Option<Runtime>) -> Result<Runtime> {
+    // Check for config override first
+    if let Some(runtime) = crate::config::get_runtime_override(requested)? {
+        return match runtime.availability() {
+            Availability::Available => Ok(runtime),
+            Availability::Missing => {
+                bail!("Selected runtime '{}' is not installed", runtime)
+            }
+            Availability::NotResponding(reason) => {
+                if runtime == Runtime::Podman && start_podman_machine()? {
+                    return Ok(runtime);
+                }
+                if wait_until_started(runtime)? {
+                    return Ok(runtime);
+                }
+                Err(not_responding(runtime, &reason))
+            }
+        };
     }
 
     // Prefer Podman if available
-    if Runtime::Podman.is_available() {
+    let podman = Runtime::Podman.availability();
+    if podman == Availability::Available {
         return Ok(Runtime::Podman);
     }
 
-    if Runtime::Docker.is_available() {
+    let docker = Runtime::Docker.availability();
+    if docker == Availability::Available {
         return Ok(Runtime::Docker);
     }
 
+    if start_podman_machine()? {
+        return Ok(Runtime::Podman);
+    }
+
+    // Installed but unreachable is a different problem from not installed
+    for (runtime, availability) in [(Runtime::Podman, &podman), (Runtime::Docker, &docker)] {
+        if matches!(availability, Availability::NotResponding(_)) && wait_until_started(runtime)? {
+            return Ok(runtime);
+        }
+    }
+    for (runtime, availability) in [(Runtime::Podman, podman), (Runtime::Docker, docker)] {
+        if let Availability::NotResponding(reason) = availability {
+            return Err(not_responding(runtime, &reason));
+        }
+    }
+
     bail!("No container runtime found.\n\n{}", install_instructions())
 }
 
@@ -144,4 +569,88 @@ mod tests {
         assert_eq!(Runtime::Docker.command(), "docker");
         assert_eq!(Runtime::Podman.command(), "podman");
     }
+
+    #[test]
+    fn test_host_info() {
+        let status = "Name:\tjail\nUid:\t1000\t1000\t1000\t1000\nGid:\t985\t985\t985\t985\n";
+        assert_eq!(parse_host_ids(status), Some((1000, 985)));
+        assert_eq!(parse_host_ids("Name:\tjail\n"), None);
+
+        assert_eq!(
+            parse_podman_socket("/run/user/1000/podman/podman.sock true\n"),
+            Some(("/run/user/1000/podman/podman.sock".to_string(), true))
+        );
+        assert_eq!(
+            parse_podman_socket("unix:///run/podman/podman.sock false"),
+            Some(("/run/podman/podman.sock".to_string(), false))
+        );
+        assert_eq!(parse_podman_socket(" false"), None);
+
+        let info = r#"{"host": {"security": {"rootless": true, "selinuxEnabled": false}}}"#;
+        assert_eq!(parse_rootless(info), Some(true));
+        assert_eq!(parse_rootless("{}"), None);
+    }
+
+    #[test]
+    fn test_volume_arg() {
+        assert_eq!(volume_arg("/src", "/repo", false, None), "/src:/repo");
+        assert_eq!(
+            volume_arg("/src", "/repo", false, Some(Relabel::Private)),
+            "/src:/repo:Z"
+        );
+        assert_eq!(
+            volume_arg(
+                "/tmp/agent.sock",
+                "/run/ssh.sock",
+                true,
+                Some(Relabel::Shared)
+            ),
+            "/tmp/agent.sock:/run/ssh.sock:ro,z"
+        );
+        assert_eq!(volume_arg("/a", "/b", true, None), "/a:/b:ro");
+    }
+
+    #[test]
+    fn test_stopped_machine() {
+        let stopped = r#"[
+            {"Name": "other", "Default": false, "Running": false, "Starting": false},
+            {"Name": "podman-machine-default", "Default": true, "Running": false, "Starting": false}
+        ]"#;
+        assert_eq!(
+            stopped_machine(stopped).as_deref(),
+            Some("podman-machine-default")
+        );
+
+        let running = r#"[{"Name": "podman-machine-default", "Default": true, "Running": true}]"#;
+        assert_eq!(stopped_machine(running), None);
+        assert_eq!(stopped_machine("[]"), None);
+        assert_eq!(stopped_machine("not json"), None);
+
+        let starting = r#"[{"Name": "podman-machine-default", "Starting": true}]"#;
+        assert!(machine_starting(starting));
+        assert!(!machine_starting(running));
+
+        assert_eq!(
+            machine_summary(stopped),
+            Some(("podman-machine-default".to_string(), false))
+        );
+        assert_eq!(
+            machine_summary(running),
+            Some(("podman-machine-default".to_string(), true))
+        );
+        assert_eq!(machine_summary("[]"), None);
+    }
+
+    #[test]
+    fn test_error_summary() {
+        assert_eq!(
+            error_summary("\nERROR: Cannot connect to the Docker daemon at unix:///var/run/docker.sock. Is the docker daemon running?\nerrors pretty printing info\n"),
+            "Cannot connect to the Docker daemon at unix:///var/run/docker.sock. Is the docker daemon running?"
+        );
+        assert_eq!(
+            error_summary("Error: unable to connect to Podman socket\n"),
+            "unable to connect to Podman socket"
+        );
+        assert_eq!(error_summary(""), "no response");
+    }
 }
@@ -0,0 +1,92 @@
+use anyhow::{bail, Result};
+use toml::{Table, Value};
+
+use crate::jail::normalize_timestamp;
+
+/// Version of the jail.toml layout written by this binary
+pub(crate) const CURRENT_VERSION: u32 = 1;
+
+/// Upgrades from each version to the next, starting at version 0 (files
+/// written before jail.toml was versioned)
+const MIGRATIONS: [fn(&mut Table); CURRENT_VERSION as usize] = [v0_to_v1];
+
+/// Bring jail.toml contents up to the current version, one version at a time.
+/// Returns whether anything was upgraded, so the file can be rewritten.
+pub(crate) fn upgrade(table: &mut Table) -> Result<bool> {
+    let version = match table.get("schema_version") {
+        None => 0,
+        Some(Value::Integer(v)) => match u32::try_from(*v) {
+            Ok(v) => v,
+            Err(_) => bail!("Invalid schema_version: {}", v),
+        },
+        Some(v) => bail!("Invalid schema_version: {}", v),
+    };
+
+    if version > CURRENT_VERSION {
+        bail!(
+            "It was written by a newer version of jail (schema version {}, this one \
+             understands up to {}). Upgrade jail to use this jail.",
+            version,
+            CURRENT_VERSION
+        );
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(table);
+    }
+    table.insert(
+        "schema_version".to_string(),
+        Value::Integer(CURRENT_VERSION.into()),
+    );
+
+    Ok(version < CURRENT_VERSION)
+}
+
+/// Unversioned files: `source` may be a single string, `ports` and
+/// `workspace_dir` may be missing, and timestamps may be Unix seconds
+fn v0_to_v1(table: &mut Table) {
+    if let Some(Value::String(source)) = table.get("source") {
+        let source = Value::Array(vec![Value::String(source.clone())]);
+        table.insert("source".to_string(), source);
+    }
+
+    table
+        .entry("ports")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    // Jails were always cloned into workspace/ before the directory was named after the repo
+    table
+        .entry("workspace_dir")
+        .or_insert_with(|| Value::String("workspace".to_string()));
+
+    for key in ["created_at", "last_used"] {
+        let timestamp = match table.get(key) {
+            Some(Value::Integer(secs)) => secs.to_string(),
+            Some(Value::String(s)) => s.clone(),
+            _ => continue,
+        };
+        table.insert(
+            key.to_string(),
+            Value::String(normalize_timestamp(&timestamp)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_versions() {
+        let mut table: Table = toml::from_str("source = [\"x\"]\nschema_version = 1").unwrap();
+        assert!(!upgrade(&mut table).unwrap());
+
+        let mut table: Table = toml::from_str("source = \"x\"\ncreated_at = 1709210096").unwrap();
+        assert!(upgrade(&mut table).unwrap());
+        assert_eq!(table["schema_version"].as_integer(), Some(1));
+        assert_eq!(table["created_at"].as_str(), Some("2024-02-29T12:34:56Z"));
+
+        let mut table: Table = toml::from_str("schema_version = 99").unwrap();
+        let err = upgrade(&mut table).unwrap_err().to_string();
+        assert!(err.contains("newer version of jail"));
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Interactive shell used inside jails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    #[default]
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Path of the shell binary inside the image
+    pub fn path(&self) -> &'static str {
+        match self {
+            Shell::Bash => "/bin/bash",
+            Shell::Zsh => "/usr/bin/zsh",
+            Shell::Fish => "/usr/bin/fish",
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_serde() {
+        #[derive(Deserialize)]
+        struct Settings {
+            shell: Shell,
+        }
+
+        let settings: Settings = toml::from_str(r#"shell = "zsh""#).unwrap();
+        assert_eq!(settings.shell, Shell::Zsh);
+        assert_eq!(settings.shell.path(), "/usr/bin/zsh");
+        assert!(toml::from_str::<Settings>(r#"shell = "tcsh""#).is_err());
+    }
+}
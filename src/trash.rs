@@ -0,0 +1,337 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use colored::Colorize;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::jail::{format_relative, is_interactive};
+use crate::output::done;
+
+/// File in a trashed jail's directory recording which jail it was
+const INFO_FILE: &str = "trash.toml";
+
+/// Days removed jails are kept when trash_retention_days isn't set
+const DEFAULT_RETENTION_DAYS: u32 = 7;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TrashInfo {
+    /// Name of the jail before it was removed
+    name: String,
+    /// When it was removed (RFC 3339)
+    removed_at: String,
+}
+
+/// A removed jail waiting in the trash
+#[derive(Debug)]
+pub(crate) struct TrashEntry {
+    /// Directory holding the jail's files
+    pub dir: PathBuf,
+    /// Name the jail had
+    pub name: String,
+    pub removed_at: DateTime<Utc>,
+}
+
+impl TrashEntry {
+    /// Directory name in the trash, which tells apart entries of the same jail
+    pub fn id(&self) -> String {
+        self.dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Free path in the trash for something of the named jail, creating the trash directory
+pub(crate) fn new_path(name: &str) -> Result<PathBuf> {
+    let trash = config::trash_dir()?;
+    std::fs::create_dir_all(&trash)
+        .with_context(|| format!("Failed to create directory: {}", trash.display()))?;
+
+    let dest = trash.join(format!(
+        "{}-{}",
+        name.replace('/', "_"),
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    if dest.exists() {
+        bail!("{} already exists", dest.display());
+    }
+    Ok(dest)
+}
+
+/// Mark a jail directory as a trash entry, before moving it to the trash
+pub(crate) fn mark(jail_dir: &Path, name: &str) -> Result<()> {
+    let info = TrashInfo {
+        name: name.to_string(),
+        removed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let path = jail_dir.join(INFO_FILE);
+    std::fs::write(&path, toml::to_string_pretty(&info)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Drop the trash marker from a jail directory
+pub(crate) fn unmark(jail_dir: &Path) {
+    let _ = std::fs::remove_file(jail_dir.join(INFO_FILE));
+}
+
+/// How long removed jails stay in the trash, or none if longer than dates go
+fn retention() -> Result<Option<TimeDelta>> {
+    let days = config::load()?
+        .trash_retention_days
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    Ok(TimeDelta::try_days(days.into()))
+}
+
+/// When a jail removed at `removed_at` is purged, or none if never
+fn purge_time(removed_at: DateTime<Utc>, retention: Option<TimeDelta>) -> Option<DateTime<Utc>> {
+    retention.and_then(|retention| removed_at.checked_add_signed(retention))
+}
+
+/// Jails removed before this are purged at `now`. A retention too long to
+/// subtract from now never expires anything.
+fn purge_cutoff(now: DateTime<Utc>, retention: Option<TimeDelta>) -> Option<DateTime<Utc>> {
+    retention.and_then(|retention| now.checked_sub_signed(retention))
+}
+
+/// Removed jails in the trash, newest first
+pub(crate) fn entries() -> Result<Vec<TrashEntry>> {
+    entries_in(&config::trash_dir()?)
+}
+
+/// Removed jails in a trash directory, newest first. Kept workspaces
+/// (`remove --keep-workspace`) have no marker and are left out.
+fn entries_in(trash: &Path) -> Result<Vec<TrashEntry>> {
+    if !trash.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for item in std::fs::read_dir(trash)
+        .with_context(|| format!("Failed to read directory: {}", trash.display()))?
+    {
+        let dir = item?.path();
+        let Ok(content) = std::fs::read_to_string(dir.join(INFO_FILE)) else {
+            continue;
+        };
+        let Ok(info) = toml::from_str::<TrashInfo>(&content) else {
+            continue;
+        };
+        let Ok(removed_at) = DateTime::parse_from_rfc3339(&info.removed_at) else {
+            continue;
+        };
+        entries.push(TrashEntry {
+            dir,
+            name: info.name,
+            removed_at: removed_at.with_timezone(&Utc),
+        });
+    }
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.removed_at));
+    Ok(entries)
+}
+
+/// Find the entry to restore: by ID, by the jail's name (its newest entry),
+/// or by part of the name when that matches a single jail
+pub(crate) fn find(query: &str) -> Result<TrashEntry> {
+    let mut entries = entries()?;
+    if let Some(pos) = entries
+        .iter()
+        .position(|e| e.id() == query || e.name == query)
+    {
+        return Ok(entries.swap_remove(pos));
+    }
+
+    entries.retain(|e| e.name.contains(query));
+    let mut names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+    match names.len() {
+        0 => bail!("No jail matching '{}' in the trash", query),
+        1 => Ok(entries.remove(0)),
+        _ => bail!(
+            "'{}' matches several jails in the trash: {}",
+            query,
+            names.join(", ")
+        ),
+    }
+}
+
+/// Permanently delete trash entries removed longer ago than the retention period.
+/// Runs before every command, so failures are ignored.
+pub fn purge_expired() {
+    let Ok(trash) = config::trash_dir() else {
+        return;
+    };
+    if !trash.exists() {
+        return;
+    }
+    let Ok(retention) = retention() else {
+        return;
+    };
+    if let Some(cutoff) = purge_cutoff(Utc::now(), retention) {
+        let _ = purge_in(&trash, cutoff);
+    }
+}
+
+/// Delete the entries in a trash directory removed before `cutoff`, returning how many
+fn purge_in(trash: &Path, cutoff: DateTime<Utc>) -> Result<usize> {
+    let mut purged = 0;
+    for entry in entries_in(trash)? {
+        if entry.removed_at < cutoff && std::fs::remove_dir_all(&entry.dir).is_ok() {
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+/// Print the removed jails in the trash
+pub fn list() -> Result<()> {
+    let entries = entries()?;
+    if entries.is_empty() {
+        println!("Trash is empty.");
+        return Ok(());
+    }
+
+    let retention = retention()?;
+    let width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+    for entry in &entries {
+        let left = purge_time(entry.removed_at, retention).map(|at| (at - Utc::now()).num_hours());
+        let purge = match left {
+            None => "kept".to_string(),
+            Some(..=0) => "purged soon".to_string(),
+            Some(left @ 1..=47) => format!("purged in {} hours", left),
+            Some(left) => format!("purged in {} days", (left + 23) / 24),
+        };
+        println!(
+            "  {:<width$}  removed {}, {}  {}",
+            entry.name.cyan(),
+            format_relative(entry.removed_at),
+            purge,
+            entry.id().dimmed(),
+            width = width
+        );
+    }
+    println!(
+        "\nRestore one with '{}'",
+        "jail trash restore <name>".cyan()
+    );
+    Ok(())
+}
+
+/// Permanently delete every removed jail in the trash
+pub fn empty(yes: bool) -> Result<()> {
+    let entries = entries()?;
+    if entries.is_empty() {
+        println!("Trash is empty.");
+        return Ok(());
+    }
+
+    let count = format!(
+        "{} removed jail{}",
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    );
+    if !yes {
+        if !is_interactive() {
+            bail!("Refusing to delete {} without --yes", count);
+        }
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Permanently delete {}?", count))
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    for entry in &entries {
+        std::fs::remove_dir_all(&entry.dir)
+            .with_context(|| format!("Failed to remove {}", entry.dir.display()))?;
+    }
+    done!("Deleted {}", count);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trashed(trash: &Path, id: &str, name: &str, removed_at: &str) {
+        let dir = trash.join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        let info = TrashInfo {
+            name: name.to_string(),
+            removed_at: removed_at.to_string(),
+        };
+        std::fs::write(dir.join(INFO_FILE), toml::to_string(&info).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_entries_and_purge() {
+        let trash = std::env::temp_dir().join(format!("jail-test-trash-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&trash);
+        trashed(
+            &trash,
+            "me_repo-20260101-120000",
+            "me/repo",
+            "2026-01-01T12:00:00Z",
+        );
+        trashed(
+            &trash,
+            "me_repo-20260105-120000",
+            "me/repo",
+            "2026-01-05T12:00:00Z",
+        );
+        trashed(
+            &trash,
+            "other-20260103-120000",
+            "other",
+            "2026-01-03T12:00:00Z",
+        );
+        // A workspace kept by `remove --keep-workspace`, which is never purged
+        std::fs::create_dir_all(trash.join("kept-20260101-000000")).unwrap();
+
+        let entries = entries_in(&trash).unwrap();
+        let ids: Vec<String> = entries.iter().map(|e| e.id()).collect();
+        assert_eq!(
+            ids,
+            [
+                "me_repo-20260105-120000",
+                "other-20260103-120000",
+                "me_repo-20260101-120000"
+            ]
+        );
+        assert_eq!(entries[0].name, "me/repo");
+
+        let cutoff = DateTime::parse_from_rfc3339("2026-01-04T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(purge_in(&trash, cutoff).unwrap(), 2);
+        assert_eq!(entries_in(&trash).unwrap().len(), 1);
+        assert!(trash.join("kept-20260101-000000").exists());
+
+        std::fs::remove_dir_all(&trash).unwrap();
+    }
+
+    #[test]
+    fn test_long_retention() {
+        let now = DateTime::parse_from_rfc3339("2026-01-04T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let week = TimeDelta::try_days(7);
+        assert_eq!(
+            purge_cutoff(now, week),
+            DateTime::parse_from_rfc3339("2025-12-28T00:00:00Z")
+                .ok()
+                .map(|cutoff| cutoff.with_timezone(&Utc))
+        );
+        assert!(purge_time(now, week).is_some());
+
+        // Days beyond the range of dates mean jails are never purged
+        let forever = TimeDelta::try_days(u32::MAX.into());
+        assert_eq!(purge_cutoff(now, forever), None);
+        assert_eq!(purge_time(now, forever), None);
+    }
+}
@@ -0,0 +1,38 @@
+//! Needs a working container runtime; run with `cargo test --features runtime-tests`.
+#![cfg(all(feature = "runtime-tests", target_os = "linux"))]
+
+use std::os::unix::fs::MetadataExt;
+use std::process::Command;
+
+#[test]
+fn test_workspace_files_owned_by_host_user() -> anyhow::Result<()> {
+    let name = format!("jail-ownership-test-{}", std::process::id());
+    let jail = jail_cli::create(
+        &name,
+        Default::default(),
+        Some(jail_cli::Preset::Minimal),
+        None,
+    )?;
+
+    let result = (|| -> anyhow::Result<()> {
+        let id = jail_cli::container_for(&jail)?;
+        let file = format!("/{}/created-in-jail", jail.metadata.workspace_dir);
+        let status = Command::new(jail.metadata.runtime.command())
+            .args(["exec", &id, "touch", &file])
+            .status()?;
+        assert!(status.success());
+
+        let workspace = jail.workspace().expect("jail has a host workspace");
+        let owner = std::fs::metadata(workspace.join("created-in-jail"))?.uid();
+        let host = std::fs::metadata(&workspace)?.uid();
+        assert_eq!(
+            owner, host,
+            "file created in the jail belongs to UID {}",
+            owner
+        );
+        Ok(())
+    })();
+
+    jail_cli::remove(jail_cli::Jail::open(&name)?, true)?;
+    result
+}